@@ -0,0 +1,988 @@
+use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize};
+use bulletproofs::r1cs::{ConstraintSystem, LinearCombination, R1CSError};
+use curve25519_dalek::scalar::Scalar;
+use crate::{collection, constrain, core, digest, ZKPNL_CONFIG};
+use crate::constants::{INTEGERIZE_FACTOR, RANGE_BITS};
+use crate::core::Integerize;
+use crate::extension::ScalarExt;
+use crate::extension::{Committed, RecordVars, SnapshotVars};
+use crate::model::*;
+
+/// public claims on every snapshot besides its pnl
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Claims {
+    /// published position size limit of each instrument
+    pub position_limit: LimitMap,
+    /// maximum gross exposure as a multiple of capital
+    pub leverage: Option<f64>,
+    /// maximum fall of cumulative pnl below its running peak
+    pub max_drawdown: Option<f64>,
+    /// rate of performance fee charged above high-water mark
+    pub performance_fee: Option<f64>,
+    /// instrument whose buy-and-hold log return is beaten by every snapshot
+    pub benchmark: Option<String>,
+    /// instruments never held at any snapshot
+    #[serde(default)]
+    pub restricted: Vec<String>,
+    /// maximum traded notional between consecutive snapshots
+    #[serde(default)]
+    pub turnover: Option<f64>,
+}
+
+impl Default for Claims {
+    fn default() -> Claims {
+        Claims {
+            position_limit: ZKPNL_CONFIG.position_limit.clone(),
+            leverage: ZKPNL_CONFIG.leverage,
+            max_drawdown: ZKPNL_CONFIG.max_drawdown,
+            performance_fee: ZKPNL_CONFIG.performance_fee,
+            benchmark: ZKPNL_CONFIG.benchmark.clone(),
+            restricted: ZKPNL_CONFIG.restricted.clone(),
+            turnover: ZKPNL_CONFIG.turnover,
+        }
+    }
+}
+
+/// constrain current snapshot to follow from previous snapshot and the records in between,
+/// shared by prover and verifier so both build the same constraint system
+pub fn constrain_link<CS: ConstraintSystem>(
+    cs: &mut CS,
+    claims: &Claims,
+    previous: Option<(&BlindedSnapshot, &SnapshotVars)>,
+    current: (&BlindedSnapshot, &SnapshotVars),
+    record_vars: &[RecordVars],
+    record_prices: &[Option<PriceMap>],
+) -> Result<(), R1CSError> {
+    let (curt, curt_vars) = current;
+    let msg = &curt.msg;
+    let trades = constrain_trades(cs, previous, curt.records(), record_vars, record_prices, &curt.market_price)?;
+    let lc_pnl = trades.pnl(&curt.market_price);
+    match (msg.pnl, &curt_vars.pnl) {
+        (Some(pnl), _) => constrain::equal(cs, lc_pnl.clone(), Scalar::integerize(pnl)),
+        (None, Some(pnl_var)) => {
+            constrain::equal(cs, lc_pnl.clone(), pnl_var.lc.clone());
+            if let Some(claim) = msg.pnl_claim {
+                constrain::within(cs, pnl_var.lc.clone(), pnl_var.value, claim.lower(), claim.upper())?;
+            }
+        },
+        _ => panic!("snapshot has neither P&L nor its commitment"),
+    }
+    let capital = snapshot_capital(msg, curt_vars);
+    // a negative capital would flip the sign of pnl behind a published return
+    if curt_vars.capital.is_some() {
+        constrain::range(cs, capital.lc.clone(), capital.value.map(|c|c as u64), RANGE_BITS)?;
+    }
+    // capital is linked to previous snapshot, otherwise a fresh hidden capital would make up any return
+    if let Some((prev, prev_vars)) = previous {
+        constrain::equal(cs, capital.lc.clone(), snapshot_capital(&prev.msg, prev_vars).lc);
+    }
+    if let Some(r) = msg.r#return {
+        let assignments = match (capital.value, pnl_value(msg, curt_vars)) {
+            (Some(capital), Some(pnl)) => Some((pnl as i128 * INTEGERIZE_FACTOR as i128, capital as i128)),
+            _ => None,
+        };
+        constrain::rounded_quotient(cs, lc_pnl.clone() * Scalar::from(INTEGERIZE_FACTOR), capital.lc.clone(), assignments, i64::integerize(r))?;
+    }
+    if let Some(benchmark) = &claims.benchmark {
+        // benchmark window of initial snapshot starts from its first record priced with benchmark
+        let start_price = match previous {
+            Some((prev, _)) => Some(*prev.market_price.get(benchmark).expect("benchmark not found in market price")),
+            None => record_prices.iter().flatten().find_map(|p|p.get(benchmark).cloned()),
+        };
+        // a claim left unconstrained would read as proven
+        let start_price = match start_price {
+            Some(start_price) => start_price,
+            None => return Err(R1CSError::GadgetError {
+                description: format!("benchmark {} has no start price, no record of initial snapshot publishes its market price", benchmark),
+            }),
+        };
+        // log return exceeds benchmark iff (capital + pnl) / capital > end price / start price
+        let end_price = *curt.market_price.get(benchmark).expect("benchmark not found in market price");
+        let public_capital = msg.capital.expect("benchmark claim needs public capital");
+        let lower = i64::integerize(public_capital * (end_price / start_price - 1.0)) + 1;
+        let pnl = pnl_value(msg, curt_vars);
+        if curt_vars.sizes.is_some() && pnl.map_or(false, |p|p < lower) {
+            panic!("log return does not exceed benchmark {}", benchmark)
+        }
+        constrain::within(cs, lc_pnl.clone(), pnl, Some(lower), None)?;
+    }
+
+    // cumulative pnl links consecutive snapshots, but snapshots taken by earlier versions don't have it
+    let prev_lc_cum = match previous {
+        Some((_, prev_vars)) => prev_vars.cum_pnl.as_ref().map(|c|c.lc.clone()),
+        None => Some(LinearCombination::default()),
+    };
+    match (&curt_vars.cum_pnl, &prev_lc_cum) {
+        (Some(cum), Some(prev_lc_cum)) => {
+            constrain::equal(cs, cum.lc.clone(), prev_lc_cum.clone() + lc_pnl);
+            // record pnl is only proven against market price signed in the record
+            for (i, (price_map, vars)) in record_prices.iter().zip(record_vars).enumerate() {
+                if let Some(price_map) = price_map {
+                    let lc_record_pnl = trades.prefix_pnl(i, price_map);
+                    constrain::equal(cs, vars.pnl.clone(), prev_lc_cum.clone() + lc_record_pnl);
+                }
+            }
+        },
+        (Some(_), None) => panic!("cumulative P&L is not linked to previous snapshot"),
+        _ => {},
+    }
+    if let Some(btc_pnl) = msg.btc_pnl {
+        let cum = match (&curt_vars.cum_pnl, &prev_lc_cum) {
+            (Some(cum), Some(_)) => cum,
+            _ => panic!("snapshot chain has no committed cumulative P&L"),
+        };
+        let end_cents = core::calc_cents(*curt.market_price.get("XBTUSD").expect("XBTUSD not found in market price"));
+        // initial snapshot converts starting capital at its own XBTUSD price
+        let (start_equity, start_cents) = match previous {
+            Some((prev, prev_vars)) => {
+                let prev_capital = snapshot_capital(&prev.msg, prev_vars);
+                let prev_cum = prev_vars.cum_pnl.as_ref().expect("previous snapshot has no committed cumulative P&L");
+                let value = prev_capital.value.and_then(|c|prev_cum.value.map(|p|c + p));
+                let price = *prev.market_price.get("XBTUSD").expect("XBTUSD not found in market price");
+                (Committed { lc: prev_capital.lc + prev_cum.lc.clone(), value }, core::calc_cents(price))
+            },
+            None => (capital.clone(), end_cents),
+        };
+        let end_equity = Committed { lc: capital.lc.clone() + cum.lc.clone(), value: capital.value.and_then(|c|cum.value.map(|p|c + p)) };
+        // btc pnl = round(100 * (end_equity * start_cents - start_equity * end_cents) / (start_cents * end_cents))
+        let n = (end_equity.lc * Scalar::from_i64(start_cents) - start_equity.lc * Scalar::from_i64(end_cents)) * Scalar::from(100u64);
+        let d = start_cents as i128 * end_cents as i128;
+        let assignments = match (start_equity.value, end_equity.value) {
+            (Some(e0), Some(e1)) => Some(((e1 as i128 * start_cents as i128 - e0 as i128 * end_cents as i128) * 100, d)),
+            _ => None,
+        };
+        constrain::rounded_quotient(cs, n, Scalar::from(d as u64).into(), assignments, i64::integerize(btc_pnl))?;
+    }
+    // cash is equity beside the portfolio, which lets solvency be proven over the portfolio at market price
+    if let Some(cash) = &curt_vars.cash {
+        let cum = match (&curt_vars.cum_pnl, &prev_lc_cum) {
+            (Some(cum), Some(_)) => cum,
+            _ => panic!("snapshot chain has no committed cumulative P&L"),
+        };
+        let (lc_value, _) = market_value(curt_vars, &curt.market_price);
+        constrain::equal(cs, cash.lc.clone(), capital.lc.clone() + cum.lc.clone() - lc_value);
+    }
+    if let Some(max_drawdown) = claims.max_drawdown {
+        let cum = match (&curt_vars.cum_pnl, &prev_lc_cum) {
+            (Some(cum), Some(_)) => cum,
+            _ => panic!("snapshot chain has no committed cumulative P&L"),
+        };
+        let prev_peak = match previous {
+            Some((_, prev_vars)) => prev_vars.peak_pnl.clone().expect("previous snapshot has no committed peak P&L"),
+            // running peak starts from zero before first snapshot
+            None => Committed { lc: LinearCombination::default(), value: cum.value.map(|_|0) },
+        };
+        let peak = curt_vars.peak_pnl.as_ref().expect("snapshot has no committed peak P&L");
+        let assignments = match (prev_peak.value, cum.value) {
+            (Some(prev_peak), Some(cum)) => Some((prev_peak, cum)),
+            _ => None,
+        };
+        let drawdown = match (peak.value, cum.value) {
+            (Some(peak), Some(cum)) => Some(peak - cum),
+            _ => None,
+        };
+        constrain::max(cs, peak.lc.clone(), prev_peak.lc, cum.lc.clone(), assignments)?;
+        constrain::at_most(cs, peak.lc.clone() - cum.lc.clone(), drawdown, i64::integerize(max_drawdown))?;
+    }
+    if let Some(rate) = claims.performance_fee {
+        let cum = match (&curt_vars.cum_pnl, &prev_lc_cum) {
+            (Some(cum), Some(_)) => cum,
+            _ => panic!("snapshot chain has no committed cumulative P&L"),
+        };
+        let fee = msg.fee.expect("snapshot has no performance fee");
+        let prev_hwm = match previous {
+            Some((_, prev_vars)) => prev_vars.hwm.clone().expect("previous snapshot has no committed high-water mark"),
+            // high-water mark starts from capital before first snapshot
+            None => capital.clone(),
+        };
+        let hwm = curt_vars.hwm.as_ref().expect("snapshot has no committed high-water mark");
+        let lc_equity = cum.lc.clone() + capital.lc.clone();
+        let assignments = match (prev_hwm.value, cum.value, capital.value) {
+            (Some(prev_hwm), Some(cum), Some(capital)) => Some((prev_hwm, capital + cum)),
+            _ => None,
+        };
+        constrain::max(cs, hwm.lc.clone(), prev_hwm.lc.clone(), lc_equity.clone(), assignments)?;
+        constrain::fee(cs, lc_equity - prev_hwm.lc, assignments.map(|(h, e)|e - h),
+                       i64::integerize(rate), INTEGERIZE_FACTOR as i64, i64::integerize(fee))?;
+    }
+
+    for (symbol, limit) in &claims.position_limit {
+        if let Some(lc_size) = curt_vars.portfolio.get(symbol) {
+            let size = curt_vars.sizes.as_ref().map(|s|s[symbol]);
+            if size.map_or(false, |s|(s as i128).abs() > *limit as i128) {
+                panic!("position size of {} exceeds limit {}", symbol, limit)
+            }
+            constrain::abs_at_most(cs, lc_size.clone(), size, *limit)?;
+        }
+    }
+    for symbol in &claims.restricted {
+        if curt_vars.sizes.as_ref().map_or(false, |s|s.get(symbol).map_or(false, |size|*size != 0)) {
+            panic!("position in restricted instrument {}", symbol)
+        }
+        // an instrument absent from trade map is never traded so its position is zero
+        if let Some(trades) = trades.trade_map.get(symbol) {
+            constrain::equal(cs, core::calc_size::<LinearCombination, Scalar>(trades), Scalar::zero());
+        }
+        if let Some(lc_size) = curt_vars.portfolio.get(symbol) {
+            constrain::equal(cs, lc_size.clone(), Scalar::zero());
+        }
+    }
+    if let Some(cap) = claims.turnover {
+        let (lc_turnover, turnover) = constrain_turnover(cs, curt_vars, curt.records(), record_vars)?;
+        let bound = i64::integerize(cap);
+        if turnover.map_or(false, |t|t > bound as i128) {
+            panic!("turnover exceeds cap {}", cap)
+        }
+        constrain::at_most(cs, lc_turnover, turnover.map(|t|t as i64), bound)?;
+    }
+    if let Some(leverage) = claims.leverage {
+        let mut lc_exposure = LinearCombination::default();
+        let mut exposure = curt_vars.sizes.as_ref().map(|_|0i128);
+        for (symbol, lc_size) in &curt_vars.portfolio {
+            let size = curt_vars.sizes.as_ref().map(|s|s[symbol]);
+            let price = curt.market_price[symbol];
+            let lc_abs = constrain::abs(cs, lc_size.clone(), size)?;
+            lc_exposure = lc_exposure + lc_abs * Scalar::integerize(price);
+            exposure = exposure.map(|e|e + (size.unwrap() as i128).abs() * i64::integerize(price) as i128);
+        }
+        let bound = i64::integerize(leverage * msg.capital.expect("leverage claim needs public capital"));
+        if exposure.map_or(false, |e|e > bound as i128) {
+            panic!("gross exposure exceeds leverage {}", leverage)
+        }
+        constrain::at_most(cs, lc_exposure, exposure.map(|e|e as i64), bound)?;
+    }
+    constrain_portfolio(cs, curt_vars, &trades.trade_map);
+    Ok(())
+}
+
+/// committed capital, or public capital as a constant
+fn snapshot_capital(msg: &SnapshotMsg, vars: &SnapshotVars) -> Committed {
+    match (&vars.capital, msg.capital) {
+        (Some(capital), _) => capital.clone(),
+        (None, Some(capital)) => Committed {
+            lc: Scalar::integerize(capital).into(),
+            value: vars.sizes.as_ref().map(|_|i64::integerize(capital)),
+        },
+        _ => panic!("snapshot has neither capital nor its commitment"),
+    }
+}
+
+/// market value of committed portfolio at market price, only known to prover
+fn market_value(vars: &SnapshotVars, price_map: &PriceMap) -> (LinearCombination, Option<i64>) {
+    let lc = vars.portfolio.iter().fold(LinearCombination::default(), |lc, (symbol, lc_size)|{
+        lc + lc_size.clone() * Scalar::integerize(price_map[symbol])
+    });
+    let value = vars.sizes.as_ref().map(|sizes|sizes.iter().map(|(symbol, size)|size * i64::integerize(price_map[symbol])).sum());
+    (lc, value)
+}
+
+/// pnl of snapshot, only known to prover if committed
+fn pnl_value(msg: &SnapshotMsg, vars: &SnapshotVars) -> Option<i64> {
+    msg.pnl.map(i64::integerize).or(vars.pnl.as_ref().and_then(|p|p.value))
+}
+
+/// committed trades following previous portfolio, with the trades up to each record.
+/// cash flow of trades at committed price is not in trade maps but summed aside
+pub struct Trades {
+    pub trade_map: LCTradeMap,
+    pub prefix_trade_maps: Vec<LCTradeMap>,
+    pub cash: LinearCombination,
+    pub prefix_cash: Vec<LinearCombination>,
+}
+
+impl Trades {
+    pub fn pnl(&self, price_map: &PriceMap) -> LinearCombination {
+        core::calc_total_pnl::<LinearCombination, Scalar>(&self.trade_map, price_map) + self.cash.clone()
+    }
+
+    /// pnl as of the i-th record
+    pub fn prefix_pnl(&self, i: usize, price_map: &PriceMap) -> LinearCombination {
+        core::calc_total_pnl::<LinearCombination, Scalar>(&self.prefix_trade_maps[i], price_map) + self.prefix_cash[i].clone()
+    }
+}
+
+/// delivery should flatten the position and inheritance should take market price.
+/// a record with committed symbol trades every instrument of market price at that time
+/// with one-hot selected quantity, so its trade map reveals nothing but the instrument universe.
+/// market price at the time of a record is bound by its hash signed in the record,
+/// or left out if it would tell hidden trade price or exact time, then the instrument universe is taken from closing market price
+pub fn constrain_trades<CS: ConstraintSystem>(
+    cs: &mut CS,
+    previous: Option<(&BlindedSnapshot, &SnapshotVars)>,
+    records: &[BlindedRecord],
+    record_vars: &[RecordVars],
+    record_prices: &[Option<PriceMap>],
+    market_price: &PriceMap,
+) -> Result<Trades, R1CSError> {
+    if record_prices.len() != records.len() || record_vars.len() != records.len() {
+        panic!("market price of records incomplete")
+    }
+    for (record, price_map) in records.iter().zip(record_prices) {
+        let signed = price_map.as_ref().map(digest::price_map_hash);
+        if signed != record.msg.price_map_hash {
+            panic!("market price mismatched its signed hash at {}", record.hash)
+        }
+    }
+    let mut lc_trade_map: LCTradeMap = LinkedHashMap::new();
+    let mut symbols: Vec<Vec<&str>> = vec![];
+    let mut selectors = vec![];
+    let mut cash = vec![];
+    for ((record, vars), price_map) in records.iter().zip(record_vars).zip(record_prices) {
+        let msg = &record.msg;
+        let price_map = price_map.as_ref();
+        let price = match &vars.price {
+            Some(price) => {
+                cash.push(cash_flow(cs, vars.qty.lc.clone(), price)?);
+                Some(0.0)
+            },
+            None => {
+                cash.push(LinearCombination::default());
+                msg.price
+            },
+        };
+        match (&vars.symbol, &msg.symbol) {
+            (Some(symbol), _) => {
+                let price_map = price_map.unwrap_or(market_price);
+                let universe: Vec<&str> = price_map.keys().map(String::as_str).collect();
+                let ids: Vec<Scalar> = universe.iter().map(|s|Scalar::from_i64(digest::symbol_id(s))).collect();
+                let index = symbol.value.map(|id|{
+                    universe.iter().position(|s|digest::symbol_id(s) == id).expect("symbol of record not found in market price")
+                });
+                let bits = constrain::one_hot(cs, symbol.lc.clone(), &ids, index)?;
+                for (s, bit) in universe.iter().zip(&bits) {
+                    let (_, _, qty) = cs.multiply(vars.qty.lc.clone(), bit.clone());
+                    let price = match (msg.r#type, price) {
+                        (TradeType::Trade, Some(price)) => price,
+                        // committed price of inherit and deliver is summed aside as cash flow
+                        (_, Some(price)) if vars.price.is_some() => price,
+                        _ => price_map[*s],
+                    };
+                    lc_trade_map.entry(s.to_string()).or_insert(vec![]).push((price, qty.into()));
+                }
+                symbols.push(universe);
+                selectors.push(Some(bits));
+            },
+            (None, Some(symbol)) => {
+                let price = price.expect("trade has neither price nor its commitment");
+                lc_trade_map.entry(symbol.clone()).or_insert(vec![]).push((price, vars.qty.lc.clone()));
+                symbols.push(vec![symbol.as_str()]);
+                selectors.push(None);
+            },
+            (None, None) => panic!("trade has neither symbol nor its commitment"),
+        }
+    }
+    let mut prefix_lc_trade_maps = collection::get_prefix_trade_maps(&symbols, &lc_trade_map);
+    if let Some((prev, prev_vars)) = previous {
+        core::inherit_portfolio(&prev_vars.portfolio, &mut lc_trade_map, &prev.market_price);
+        for prefix_lc_trade_map in prefix_lc_trade_maps.iter_mut() {
+            core::inherit_portfolio(&prev_vars.portfolio, prefix_lc_trade_map, &prev.market_price);
+        }
+    }
+    for (i, record) in records.iter().enumerate() {
+        let prefix_lc_trade_map = &prefix_lc_trade_maps[i];
+        let size_of = |symbol: &str| core::calc_size::<LinearCombination, Scalar>(&prefix_lc_trade_map[symbol]);
+        match (record.msg.r#type, &selectors[i]) {
+            (TradeType::Deliver, None) => constrain::equal(cs, size_of(symbols[i][0]), Scalar::zero()),
+            (TradeType::Deliver, Some(bits)) => {
+                let mut lc_size = LinearCombination::default();
+                for (symbol, bit) in symbols[i].iter().zip(bits) {
+                    let (_, _, selected) = cs.multiply(bit.clone(), size_of(symbol));
+                    lc_size = lc_size + selected;
+                }
+                constrain::equal(cs, lc_size, Scalar::zero());
+            },
+            _ => {},
+        }
+        // inherit and deliver of public symbol publish their price, so it is checked in the clear rather than constrained
+        // against market price signed in the record. records of earlier versions have no signed market price to check
+        if let (TradeType::Inherit, None, Some(price_map)) | (TradeType::Deliver, None, Some(price_map)) = (record.msg.r#type, &selectors[i], &record_prices[i]) {
+            if price_map.get(symbols[i][0]) != record.msg.price.as_ref() {
+                panic!("market price mismatched at {}", record.hash)
+            }
+        }
+    }
+    let prefix_cash: Vec<LinearCombination> = cash.iter()
+        .scan(LinearCombination::default(), |acc, cash|{
+            *acc = acc.clone() + cash.clone();
+            Some(acc.clone())
+        }).collect();
+    Ok(Trades {
+        trade_map: lc_trade_map,
+        prefix_trade_maps: prefix_lc_trade_maps,
+        cash: prefix_cash.last().cloned().unwrap_or_default(),
+        prefix_cash,
+    })
+}
+
+/// cash flow of trading at committed price, which should be non-negative
+fn cash_flow<CS: ConstraintSystem>(cs: &mut CS, qty: LinearCombination, price: &Committed) -> Result<LinearCombination, R1CSError> {
+    constrain::range(cs, price.lc.clone(), price.value.map(|p|p as u64), RANGE_BITS)?;
+    let (_, _, product) = cs.multiply(qty, price.lc.clone());
+    Ok(-LinearCombination::from(product))
+}
+
+/// traded notional sum(|qty| * price) of trade records, inheritance and delivery are not trading
+fn constrain_turnover<CS: ConstraintSystem>(cs: &mut CS, vars: &SnapshotVars, records: &[BlindedRecord], record_vars: &[RecordVars])
+                                            -> Result<(LinearCombination, Option<i128>), R1CSError> {
+    let mut lc_turnover = LinearCombination::default();
+    // snapshot variables tell prover from verifier even if there is no record
+    let mut turnover = vars.sizes.as_ref().map(|_|0i128);
+    for (record, vars) in records.iter().zip(record_vars) {
+        if let TradeType::Trade = record.msg.r#type {
+            let lc_abs = constrain::abs(cs, vars.qty.lc.clone(), vars.qty.value)?;
+            let (lc_notional, price) = match (&vars.price, record.msg.price) {
+                (Some(price), _) => {
+                    let (_, _, product) = cs.multiply(lc_abs, price.lc.clone());
+                    (product.into(), price.value)
+                },
+                (None, Some(price)) => (lc_abs * Scalar::integerize(price), Some(i64::integerize(price))),
+                (None, None) => panic!("trade has neither price nor its commitment"),
+            };
+            lc_turnover = lc_turnover + lc_notional;
+            turnover = turnover.map(|t|t + (vars.qty.value.unwrap() as i128).abs() * price.unwrap() as i128);
+        }
+    }
+    Ok((lc_turnover, turnover))
+}
+
+/// committed portfolio should be the sum of committed trades, and size of an instrument never traded is zero
+pub fn constrain_portfolio<CS: ConstraintSystem>(cs: &mut CS, vars: &SnapshotVars, lc_trade_map: &LCTradeMap) {
+    let expected_lc_port_map = core::calc_portfolio::<LinearCombination, Scalar>(lc_trade_map);
+    for (symbol, lc_size) in &vars.portfolio {
+        constrain::equal(cs, lc_size.clone(), expected_lc_port_map.get(symbol).cloned().unwrap_or_default());
+    }
+}
+
+/// enforce market value of committed portfolio at signed market price plus cash >= liabilities,
+/// where snapshot proof links cash to capital, cumulative pnl and the same portfolio
+pub fn constrain_solvency<CS: ConstraintSystem>(cs: &mut CS, snapshot: &BlindedSnapshot, vars: &SnapshotVars, liabilities: f64) -> Result<(), R1CSError> {
+    if snapshot.msg.price_map_hash.is_none() {
+        panic!("snapshot has no signed market price")
+    }
+    let cash = vars.cash.as_ref().expect("snapshot has no committed cash");
+    let (lc_value, value) = market_value(vars, &snapshot.market_price);
+    let assets = match (value, cash.value) {
+        (Some(value), Some(cash)) => Some(value + cash),
+        _ => None,
+    };
+    constrain::within(cs, lc_value + cash.lc.clone(), assets, Some(i64::integerize(liabilities)), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::Deintegerize;
+    use crate::extension::{ProverExt, R1CSConfig};
+    use crate::testing::{self, Book};
+    use super::*;
+
+    /// long XBTUSD and short ETHUSDT at the initial snapshot
+    fn book() -> Book {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, &[("XBTUSD", 9000.0), ("ETHUSDT", 200.0)])
+            .trade("ETHUSDT", -50, 210.0, &[("XBTUSD", 9100.0), ("ETHUSDT", 210.0)])
+            .snapshot(&[("XBTUSD", 9500.0), ("ETHUSDT", 190.0)]);
+        book
+    }
+
+    fn limits(limits: &[(&str, u64)]) -> Claims {
+        let mut claims = testing::no_claims();
+        claims.position_limit = limits.iter().map(|(symbol, limit)|(symbol.to_string(), *limit)).collect();
+        claims
+    }
+
+    #[test]
+    fn position_limit_verifies_within_limit() {
+        let proof = book().prove(0, limits(&[("XBTUSD", 100), ("ETHUSDT", 50), ("ETHUSD", 0)]));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn position_limit_fails_verifier_with_tighter_limit() {
+        let mut proof = book().prove(0, limits(&[("ETHUSDT", 50)]));
+        proof.claims = limits(&[("ETHUSDT", 49)]);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    #[should_panic(expected = "position size of ETHUSDT exceeds limit 49")]
+    fn position_limit_refuses_to_prove_beyond_limit() {
+        book().prove(0, limits(&[("ETHUSDT", 49)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "beyond signed 64-bit range")]
+    fn position_limit_refuses_limit_beyond_signed_range() {
+        book().prove(0, limits(&[("XBTUSD", u64::MAX)]));
+    }
+
+    #[test]
+    fn pnl_claim_verifies_hidden_pnl_within_claim() {
+        let mut book = book();
+        book.disclose(PnlClaim { lower: Some(0.0), upper: None });
+        let proof = book.prove(0, testing::no_claims());
+        assert!(proof.current_snapshot.msg.pnl.is_none());
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn pnl_claim_fails_verifier_when_claim_excludes_pnl() {
+        let mut book = book();
+        book.disclose(PnlClaim { lower: Some(0.0), upper: None });
+        let mut proof = book.prove(0, testing::no_claims());
+        proof.current_snapshot.msg.pnl_claim = Some(PnlClaim { lower: None, upper: Some(0.0) });
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    fn leverage(leverage: f64) -> Claims {
+        let mut claims = testing::no_claims();
+        claims.leverage = Some(leverage);
+        claims
+    }
+
+    #[test]
+    fn leverage_verifies_gross_exposure_within_bound() {
+        // 100 * 9500 + 50 * 190 against capital of 3_000_000
+        let proof = book().prove(0, leverage(0.32));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn leverage_fails_verifier_with_tighter_bound() {
+        let mut proof = book().prove(0, leverage(0.32));
+        proof.claims = leverage(0.3);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    #[should_panic(expected = "gross exposure exceeds leverage 0.3")]
+    fn leverage_refuses_to_prove_beyond_bound() {
+        book().prove(0, leverage(0.3));
+    }
+
+    fn max_drawdown(max_drawdown: f64) -> Claims {
+        let mut claims = testing::no_claims();
+        claims.max_drawdown = Some(max_drawdown);
+        claims
+    }
+
+    /// peak is taken at the first snapshot and the second one falls below it
+    fn drawdown_book() -> (Book, f64) {
+        let mut book = book();
+        book.snapshot(&[("XBTUSD", 9200.0), ("ETHUSDT", 200.0)]).track_peak();
+        let snapshot = &book.album[1].snapshot_blnd;
+        let drawdown = snapshot.peak_pnl.as_ref().unwrap().value - snapshot.cum_pnl.as_ref().unwrap().value;
+        assert!(drawdown > 0);
+        (book, drawdown.deintegerize())
+    }
+
+    #[test]
+    fn max_drawdown_verifies_initial_snapshot_at_peak() {
+        let mut book = book();
+        book.track_peak();
+        let proof = book.prove(0, max_drawdown(0.0));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn max_drawdown_verifies_drawdown_within_bound() {
+        let (book, drawdown) = drawdown_book();
+        let proof = book.prove(1, max_drawdown(drawdown));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn max_drawdown_fails_drawdown_beyond_bound() {
+        let (book, drawdown) = drawdown_book();
+        let proof = book.prove(1, max_drawdown(drawdown - 0.01));
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn max_drawdown_fails_verifier_with_tighter_bound() {
+        let (book, drawdown) = drawdown_book();
+        let mut proof = book.prove(1, max_drawdown(drawdown));
+        proof.claims = max_drawdown(drawdown - 0.01);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    /// replace cumulative pnl committed in i-th record of a snapshot
+    fn recommit_pnl(snapshot: &mut Snapshot, i: usize, pnl: f64) {
+        let (pnl_cmt, pnl_blnd) = R1CSConfig::default().make_prover().commit_quantity(i64::integerize(pnl));
+        let record = &mut snapshot.snapshot_blnd.records[i];
+        record.msg.pnl = pnl_cmt;
+        record.trade.pnl = pnl;
+        record.trade.pnl_blnd = pnl_blnd;
+        snapshot.msg.records[i] = BlindedRecord::from(&*record);
+    }
+
+    #[test]
+    fn record_pnl_fails_when_off_by_one_cent() {
+        let mut book = book();
+        let pnl = book.album[0].snapshot_blnd.records[1].trade.pnl + 0.01;
+        recommit_pnl(&mut book.album[0], 1, pnl);
+        let proof = book.prove(0, testing::no_claims());
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn record_pnl_verifies_when_recommitted_to_same_value() {
+        let mut book = book();
+        let pnl = book.album[0].snapshot_blnd.records[0].trade.pnl;
+        recommit_pnl(&mut book.album[0], 0, pnl);
+        let proof = book.prove(0, testing::no_claims());
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    const PRICES: &[(&str, f64)] = &[("XBTUSD", 9500.0), ("ETHUSDT", 190.0)];
+
+    #[test]
+    fn deliver_verifies_flattened_position() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES)
+            .commit(TradeType::Deliver, "XBTUSD", 0, 9500.0, PRICES)
+            .snapshot(PRICES);
+        assert_eq!(book.album[0].snapshot_blnd.portfolio["XBTUSD"], 0);
+        let proof = book.prove(0, testing::no_claims());
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn deliver_fails_when_position_is_left_open() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES)
+            .trade("XBTUSD", -40, 9500.0, PRICES);
+        let record = book.records.last_mut().unwrap();
+        record.msg.r#type = TradeType::Deliver;
+        record.trade.r#type = TradeType::Deliver;
+        book.snapshot(PRICES);
+        let proof = book.prove(0, testing::no_claims());
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn inherit_verifies_at_market_price() {
+        let mut book = Book::new();
+        book.commit(TradeType::Inherit, "ETHUSDT", 5, 190.0, PRICES).snapshot(PRICES);
+        let proof = book.prove(0, testing::no_claims());
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    #[should_panic(expected = "market price mismatched at")]
+    fn inherit_refuses_price_off_market() {
+        let mut book = Book::new();
+        book.commit(TradeType::Inherit, "ETHUSDT", 5, 180.0, PRICES).snapshot(PRICES);
+        book.prove(0, testing::no_claims());
+    }
+
+    #[test]
+    fn hidden_price_verifies_pnl_at_committed_price() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).hide_price(9000.0)
+            .trade("XBTUSD", -40, 9400.0, PRICES).hide_price(9400.0)
+            .snapshot(PRICES);
+        let proof = book.prove(0, testing::no_claims());
+        assert!(proof.current_snapshot.records().iter().all(|r|r.msg.price.is_none()));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn hidden_price_fails_when_recommitted_after_snapshot() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).hide_price(9000.0).snapshot(PRICES);
+        let snapshot = &mut book.album[0];
+        let (price_cmt, price_blnd) = R1CSConfig::default().make_prover().commit_quantity(i64::integerize(8900.0));
+        let record = &mut snapshot.snapshot_blnd.records[0];
+        record.msg.price_cmt = Some(price_cmt);
+        record.trade.price = 8900.0;
+        record.trade.price_blnd = Some(price_blnd);
+        snapshot.msg.records[0] = BlindedRecord::from(&*record);
+        let proof = book.prove(0, testing::no_claims());
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn hidden_symbol_verifies_trade_selected_from_universe() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).hide_symbol()
+            .trade("ETHUSDT", -50, 200.0, PRICES).hide_symbol()
+            .snapshot(PRICES);
+        let proof = book.prove(0, testing::no_claims());
+        assert!(proof.current_snapshot.records().iter().all(|r|r.msg.symbol.is_none()));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn hidden_symbol_fails_when_recommitted_after_snapshot() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).hide_symbol().snapshot(PRICES);
+        let snapshot = &mut book.album[0];
+        let record = &mut snapshot.snapshot_blnd.records[0];
+        testing::recommit_symbol(record, "ETHUSDT");
+        snapshot.msg.records[0] = BlindedRecord::from(&*record);
+        let proof = book.prove(0, testing::no_claims());
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    fn restricted(symbols: &[&str]) -> Claims {
+        let mut claims = testing::no_claims();
+        claims.restricted = symbols.iter().map(|s|s.to_string()).collect();
+        claims
+    }
+
+    #[test]
+    fn restricted_verifies_instrument_never_traded_or_closed() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES)
+            .trade("ETHUSDT", -50, 200.0, PRICES)
+            .trade("ETHUSDT", 50, 190.0, PRICES)
+            .snapshot(PRICES);
+        let proof = book.prove(0, restricted(&["ETHUSDT", "DOGEUSD"]));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn restricted_verifies_hidden_symbol() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).hide_symbol().snapshot(PRICES);
+        let proof = book.prove(0, restricted(&["ETHUSDT"]));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    #[should_panic(expected = "position in restricted instrument ETHUSDT")]
+    fn restricted_refuses_to_prove_held_instrument() {
+        book().prove(0, restricted(&["ETHUSDT"]));
+    }
+
+    #[test]
+    fn restricted_fails_verifier_on_held_instrument() {
+        let mut proof = book().prove(0, testing::no_claims());
+        proof.claims = restricted(&["ETHUSDT"]);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn restricted_fails_verifier_on_held_hidden_symbol() {
+        let mut book = Book::new();
+        book.trade("ETHUSDT", -50, 200.0, PRICES).hide_symbol().snapshot(PRICES);
+        let mut proof = book.prove(0, testing::no_claims());
+        proof.claims = restricted(&["ETHUSDT"]);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn return_verifies_with_hidden_capital() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).snapshot(PRICES).hide_capital(3_000_000.0)
+            .snapshot(&[("XBTUSD", 9000.0), ("ETHUSDT", 190.0)]).hide_capital(3_000_000.0);
+        for i in 0..2 {
+            let proof = book.prove(i, testing::no_claims());
+            assert!(proof.current_snapshot.msg.capital.is_none() && proof.current_snapshot.msg.pnl.is_none());
+            assert!(testing::verifies(||proof.verify_r1cs()));
+        }
+        // 100 * (9000 - 9500) on capital of 3_000_000, rounded to integerized unit
+        assert_eq!(book.album[1].msg.r#return, Some(-0.016666667));
+    }
+
+    #[test]
+    fn return_fails_verifier_with_tampered_return() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).snapshot(PRICES).hide_capital(3_000_000.0);
+        let mut proof = book.prove(0, testing::no_claims());
+        proof.current_snapshot.msg.r#return = proof.current_snapshot.msg.r#return.map(|r|r + 1e-9);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn return_fails_verifier_with_capital_changed_since_previous_snapshot() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).snapshot(PRICES).hide_capital(3_000_000.0)
+            .snapshot(&[("XBTUSD", 9000.0), ("ETHUSDT", 190.0)]).hide_capital(2_000_000.0);
+        let proof = book.prove(1, testing::no_claims());
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn return_fails_verifier_with_negative_capital() {
+        // pnl of -50000 on capital of -3_000_000 would publish a positive return
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9500.0, PRICES).snapshot(&[("XBTUSD", 9000.0), ("ETHUSDT", 190.0)]).hide_capital(-3_000_000.0);
+        assert!(book.album[0].msg.r#return.unwrap() > 0.0);
+        let proof = book.prove(0, testing::no_claims());
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    /// XBTUSD rises from 9500 to 10000 between the two snapshots
+    fn btc_book() -> Book {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).snapshot(PRICES).btc_pnl()
+            .snapshot(&[("XBTUSD", 10000.0), ("ETHUSDT", 190.0)]).btc_pnl();
+        book
+    }
+
+    #[test]
+    fn btc_pnl_verifies_initial_and_later_snapshot() {
+        let book = btc_book();
+        // initial snapshot: 100 * (9500 - 9000) / 9500
+        assert!((book.album[0].msg.btc_pnl.unwrap() - 50_000.0 / 9500.0).abs() < 1e-6);
+        // equity of 3_100_000 at 10000 against 3_050_000 at 9500
+        assert!((book.album[1].msg.btc_pnl.unwrap() - (310.0 - 3_050_000.0 / 9500.0)).abs() < 1e-6);
+        for i in 0..2 {
+            let proof = book.prove(i, testing::no_claims());
+            assert!(testing::verifies(||proof.verify_r1cs()));
+        }
+    }
+
+    #[test]
+    fn btc_pnl_fails_verifier_with_tampered_btc_pnl() {
+        let mut proof = btc_book().prove(1, testing::no_claims());
+        proof.current_snapshot.msg.btc_pnl = proof.current_snapshot.msg.btc_pnl.map(|b|b + 0.01);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn btc_pnl_fails_verifier_with_tampered_previous_price() {
+        let mut proof = btc_book().prove(1, testing::no_claims());
+        proof.previous_snapshot.as_mut().unwrap().market_price.insert("XBTUSD".to_string(), 9400.0);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+        assert!(!testing::verifies(||proof.verify_hash()));
+    }
+
+    fn turnover(cap: f64) -> Claims {
+        let mut claims = testing::no_claims();
+        claims.turnover = Some(cap);
+        claims
+    }
+
+    #[test]
+    fn turnover_verifies_notional_within_cap() {
+        // 100 * 9000 + 50 * 210
+        let proof = book().prove(0, turnover(910_500.0));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn turnover_fails_verifier_with_tighter_cap() {
+        let mut proof = book().prove(0, turnover(910_500.0));
+        proof.claims = turnover(910_499.0);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    #[should_panic(expected = "turnover exceeds cap 910499")]
+    fn turnover_refuses_to_prove_beyond_cap() {
+        book().prove(0, turnover(910_499.0));
+    }
+
+    #[test]
+    fn turnover_verifies_hidden_price() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", -100, 9000.0, PRICES).hide_price(9000.0).snapshot(PRICES);
+        let proof = book.prove(0, turnover(900_000.0));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+        let mut proof = book.prove(0, turnover(900_000.0));
+        proof.claims = turnover(899_999.0);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn turnover_verifies_snapshot_without_records() {
+        let mut book = book();
+        book.snapshot(PRICES);
+        let proof = book.prove(1, turnover(0.0));
+        assert!(proof.current_snapshot.records().is_empty());
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    fn benchmark(symbol: &str) -> Claims {
+        let mut claims = testing::no_claims();
+        claims.benchmark = Some(symbol.to_string());
+        claims
+    }
+
+    #[test]
+    fn benchmark_verifies_return_above_benchmark_since_first_record() {
+        // ETHUSDT falls from 200 at first record to 190 while the book gains
+        let proof = book().prove(0, benchmark("ETHUSDT"));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn benchmark_verifies_against_previous_snapshot() {
+        let mut book = book();
+        book.snapshot(&[("XBTUSD", 9600.0), ("ETHUSDT", 180.0)]);
+        let proof = book.prove(1, benchmark("ETHUSDT"));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    #[should_panic(expected = "log return does not exceed benchmark XBTUSD")]
+    fn benchmark_refuses_to_prove_below_benchmark() {
+        book().prove(0, benchmark("XBTUSD"));
+    }
+
+    #[test]
+    fn benchmark_fails_verifier_with_other_benchmark() {
+        let mut proof = book().prove(0, benchmark("ETHUSDT"));
+        proof.claims = benchmark("XBTUSD");
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    #[should_panic(expected = "benchmark ETHUSDT has no start price")]
+    fn benchmark_refuses_initial_snapshot_without_record_price() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).hide_price(9000.0).snapshot(PRICES);
+        book.prove(0, benchmark("ETHUSDT"));
+    }
+
+    #[test]
+    fn benchmark_fails_verifier_without_start_price() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).hide_price(9000.0).snapshot(PRICES);
+        let mut proof = book.prove(0, testing::no_claims());
+        proof.claims = benchmark("ETHUSDT");
+        assert!(proof.verify_r1cs().is_err());
+    }
+
+    fn performance_fee(rate: f64) -> Claims {
+        let mut claims = testing::no_claims();
+        claims.performance_fee = Some(rate);
+        claims
+    }
+
+    /// equity rises above capital, then falls below the high-water mark
+    fn fee_book() -> Book {
+        let mut book = book();
+        book.snapshot(&[("XBTUSD", 9200.0), ("ETHUSDT", 200.0)]).track_hwm(0.2);
+        book
+    }
+
+    #[test]
+    fn performance_fee_verifies_fee_above_and_below_mark() {
+        let book = fee_book();
+        assert!(book.album[0].msg.fee.unwrap() > 0.0);
+        assert_eq!(book.album[1].msg.fee, Some(0.0));
+        for i in 0..2 {
+            let proof = book.prove(i, performance_fee(0.2));
+            assert!(testing::verifies(||proof.verify_r1cs()));
+        }
+    }
+
+    #[test]
+    fn performance_fee_fails_with_tampered_fee() {
+        let mut book = fee_book();
+        book.album[0].msg.fee = book.album[0].msg.fee.map(|fee|fee + 0.01);
+        let proof = book.prove(0, performance_fee(0.2));
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn performance_fee_fails_verifier_with_other_rate() {
+        let mut proof = fee_book().prove(0, performance_fee(0.2));
+        proof.claims = performance_fee(0.1);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+}
\ No newline at end of file
@@ -1,6 +1,10 @@
+use chrono::{DateTime, Duration, FixedOffset};
+use rand::Rng;
+use rand::seq::SliceRandom;
 use crate::*;
 use crate::model::*;
-use crate::proof::ZKPNLProof;
+use crate::core::{Deintegerize, Integerize};
+use crate::proof::{AggregateProof, AuditResponse, FundProof, HitRateProof, PortfolioOpening, SolvencyProof, TradeInclusion, WindowProof, ZKPNLProof};
 use crate::time::TimeRange;
 
 pub fn commit(r#type: TradeType, symbol: &str, qty: i64, price: f64) -> Result<()> {
@@ -30,7 +34,7 @@ pub fn commit(r#type: TradeType, symbol: &str, qty: i64, price: f64) -> Result<(
 
 pub fn snapshot() -> Result<()> {
     let time = time::now();
-    let records: Vec<Record> = db::read_record()?;
+    let mut records: Vec<Record> = db::read_record()?;
     if records.is_empty() {
         println!("{}", "no record. please commit first.");
         return Ok(())
@@ -39,12 +43,18 @@ pub fn snapshot() -> Result<()> {
     let mut album = db::read_album()?;
     let symbols = collection::get_symbols(&market_prices);
     let price_map = api::fetch_price_map(symbols)?;
-    let snapshot = model::Snapshot::new(time, &album, records, &price_map)?;
+    let pads = pad_records(time, &album, &mut records, &mut market_prices, &price_map, ZKPNL_CONFIG.padding)?;
+    let snapshot = model::Snapshot::new(time, &album, records.clone(), &price_map)?;
+    if pads > 0 {
+        println!("padded {} dummy records", pads);
+        db::write_record(records)?;
+    }
 
     let start = album.last()
-        .map_or_else(||snapshot.snapshot_blnd.records.first().unwrap().msg.time, |a|a.msg.time);
-    let pnl = snapshot.msg.pnl;
-    let log_return = snapshot.msg.log_return;
+        .map_or_else(||snapshot.snapshot_blnd.records.first().unwrap().trade.time, |a|a.msg.time);
+    let pnl = snapshot.pnl();
+    let log_return = snapshot.log_return();
+    let btc_pnl = snapshot.msg.btc_pnl;
     let port = snapshot.snapshot_blnd.portfolio.clone();
     let hash = snapshot.hash.clone();
     let sig = snapshot.sig.clone();
@@ -60,50 +70,378 @@ pub fn snapshot() -> Result<()> {
         println!("{:^25}|{:^8}", symbol, size);
     }
     println!("\nFrom\t\t{}\nTo\t\t{}\nP&L\t\t{}\nLog Return\t{}", start, time, pnl, log_return);
+    if let Some(btc_pnl) = btc_pnl {
+        println!("P&L (BTC)\t{}", btc_pnl);
+    }
     println!("\nhash: {}\nsig: {}", hash, sig);
     Ok(())
 }
 
+/// pad records since last snapshot up to padding count with zero quantity trades at random times of the period,
+/// so the number of records in a snapshot does not tell trading frequency. real records of the period are linked
+/// and signed again around the dummies, whose price and symbol are committed as those of real trades
+fn pad_records(time: DateTime<FixedOffset>, album: &[Snapshot], records: &mut Vec<Record>,
+               market_prices: &mut Vec<MarketPrice>, price_map: &PriceMap, padding: usize) -> Result<usize> {
+    let since = album.last().map(|s|s.msg.time);
+    let first = records.iter().position(|r|since.map_or(true, |t|r.trade.time > t)).unwrap_or(records.len());
+    if records.len() - first >= padding {
+        return Ok(0)
+    }
+    if records[first..].iter().any(|r|r.msg.symbol.is_some() || r.msg.price.is_some()) {
+        return Err("records with public price or symbol would tell dummies apart, please take a snapshot before padding".into())
+    }
+    let period = records.split_off(first);
+    let start = since.unwrap_or_else(||period[0].trade.time);
+    // nanosecond offsets so dummies don't share the sub-millisecond digits of start
+    let span = (time - start).num_nanoseconds().unwrap_or(i64::MAX);
+    let mut rng = rand::thread_rng();
+    let mut dummy_times: Vec<DateTime<FixedOffset>> = (period.len()..padding)
+        .map(|_|start + Duration::nanoseconds(rng.gen_range(1, span.max(2))))
+        .filter(|t|period.iter().all(|r|r.trade.time != *t))
+        .collect();
+    dummy_times.sort();
+    dummy_times.dedup();
+    let symbols: Vec<&str> = price_map.keys().map(String::as_str).collect();
+    let mut period = period.into_iter().peekable();
+    let mut dummies = dummy_times.iter().peekable();
+    loop {
+        let is_dummy = match (period.peek(), dummies.peek()) {
+            (Some(record), Some(dummy_time)) => **dummy_time < record.trade.time,
+            (None, Some(_)) => true,
+            (Some(_), None) => false,
+            (None, None) => break,
+        };
+        if is_dummy {
+            let dummy_time = *dummies.next().unwrap();
+            let symbol = symbols.choose(&mut rng).unwrap();
+            let dummy = Record::new(dummy_time, symbol, 0, price_map[*symbol], TradeType::Trade, records, price_map)?;
+            records.push(dummy);
+            market_prices.push(MarketPrice { time: dummy_time, market_price: price_map.clone() });
+        } else {
+            let mut record = period.next().unwrap();
+            let genesis_hash = digest::sha256(&ZKPNL_CONFIG.transcript);
+            record.relink(records.last().map_or(&genesis_hash, |r|&r.hash))?;
+            records.push(record);
+        }
+    }
+    market_prices.sort_by_key(|mp|mp.time);
+    Ok(dummy_times.len())
+}
+
 pub fn prove() -> Result<()> {
     let album = db::read_album()?;
     if album.is_empty() {
         println!("{}", "no snapshot. please take snapshot first.");
         return Ok(())
     }
+    let market_prices = db::read_price()?;
     println!("{}", "generating initial snapshot proof");
-    let proof = ZKPNLProof::new(None, album.first().unwrap());
+    let proof = ZKPNLProof::new(None, album.first().unwrap(), &market_prices);
     db::write_proof(proof)?;
     println!("{}", "generating snapshot proof");
     let album_tail = album.split_first().unwrap().1;
     for (previous, current) in album.iter().zip(album_tail) {
-        let proof = ZKPNLProof::new(Some(previous), current);
+        let proof = ZKPNLProof::new(Some(previous), current, &market_prices);
         db::write_proof(proof)?;
     }
     println!("Write all {} snapshot proofs completed", album.len());
+    if let Some(rate) = ZKPNL_CONFIG.performance_fee {
+        let reports = album.iter().filter_map(|s|report::FeeReport::new(s, rate)).collect();
+        db::write_fee_report(reports)?;
+    }
+    Ok(())
+}
+
+pub fn prove_aggregate() -> Result<()> {
+    let album = db::read_album()?;
+    if album.is_empty() {
+        println!("{}", "no snapshot. please take snapshot first.");
+        return Ok(())
+    }
+    let market_prices = db::read_price()?;
+    println!("generating aggregate proof of {} snapshots", album.len());
+    let proof = AggregateProof::new(&album, &market_prices);
+    db::write_aggregate_proof(proof)?;
+    Ok(())
+}
+
+/// snapshot is specified by hash prefix as shown in snapshot table
+pub fn open(snapshot: &str, symbols: &[&str]) -> Result<()> {
+    let album = db::read_album()?;
+    let snapshot = match album.iter().find(|s|s.hash.starts_with(snapshot)) {
+        Some(snapshot) => snapshot,
+        None => return Err("snapshot not found".into()),
+    };
+    if let Some(symbol) = symbols.iter().find(|s|!snapshot.snapshot_blnd.portfolio.contains_key(**s)) {
+        return Err(format!("{} not found in snapshot portfolio", symbol).into())
+    }
+    let opening = PortfolioOpening::new(snapshot, symbols);
+    db::write_opening(opening)?;
+    Ok(())
+}
+
+/// record is specified by hash prefix, its snapshot should commit records by merkle root
+pub fn prove_trade(record: &str) -> Result<()> {
+    let album = db::read_album()?;
+    let found = album.iter().find_map(|s|{
+        s.snapshot_blnd.records.iter().find(|r|r.hash.starts_with(record)).map(|r|(s, r))
+    });
+    let (snapshot, record) = match found {
+        Some(found) => found,
+        None => return Err("record not found in snapshots".into()),
+    };
+    if snapshot.msg.records_root.is_none() {
+        return Err("snapshot of record has no records root, please enable merkle_records before taking snapshot".into())
+    }
+    let path = snapshot.merkle_path(&record.hash).unwrap();
+    let proof = TradeInclusion::new(snapshot, record, path);
+    db::write_trade_inclusion(proof)?;
+    Ok(())
+}
+
+/// challenge is chosen by auditor, e.g. hash of a snapshot taken after the request
+pub fn audit(challenge: &str, count: usize) -> Result<()> {
+    let records = db::read_record()?;
+    if records.is_empty() {
+        println!("{}", "no record to audit.");
+        return Ok(())
+    }
+    println!("opening {} records sampled by challenge", count.min(records.len()));
+    let response = AuditResponse::new(&records, challenge, count);
+    db::write_audit_response(response)?;
+    Ok(())
+}
+
+/// solvency of latest snapshot against liabilities in USD
+pub fn prove_solvency(liabilities: f64) -> Result<()> {
+    let album = db::read_album()?;
+    let snapshot = match album.last() {
+        Some(snapshot) => snapshot,
+        None => {
+            println!("{}", "no snapshot. please take snapshot first.");
+            return Ok(())
+        }
+    };
+    let cash = match &snapshot.snapshot_blnd.cash {
+        Some(cash) => cash.value,
+        None => return Err("snapshot has no committed cash, please take a new snapshot".into()),
+    };
+    let blnd = &snapshot.snapshot_blnd;
+    let market_value: i64 = blnd.portfolio.iter().map(|(symbol, size)|size * i64::integerize(blnd.market_price[symbol])).sum();
+    if market_value + cash < i64::integerize(liabilities) {
+        return Err("assets are below liabilities, solvency proof will not verify".into())
+    }
+    println!("{}", "generating solvency proof");
+    let proof = SolvencyProof::new(snapshot, liabilities);
+    db::write_solvency_proof(proof)?;
+    Ok(())
+}
+
+/// traders are given as pairs of album file and public key shown by `show key`
+pub fn prove_fund(traders: &[(&str, &str)]) -> Result<()> {
+    let mut latest = vec![];
+    for (album_path, pub_key) in traders {
+        let album = db::read_album_from(album_path)?;
+        let snapshot = match album.last() {
+            Some(snapshot) => snapshot.clone(),
+            None => return Err(format!("no snapshot in album {}", album_path).into()),
+        };
+        let pk = sig::get_pub_key_from_str(pub_key)?;
+        sig::verify_sig_with_pk(&pk, &snapshot)?;
+        // cumulative pnl is the sum of pnl published along the album
+        if album.iter().any(|s|s.msg.pnl.is_some()) {
+            return Err(format!("P&L of {} is published in its album, please set pnl_disclosure and start a new album", album_path).into())
+        }
+        latest.push((pub_key.to_string(), snapshot));
+    }
+    println!("generating fund proof of {} traders", latest.len());
+    let proof = FundProof::new(&latest)?;
+    db::write_fund_proof(proof)?;
+    Ok(())
+}
+
+/// snapshots are specified by hash prefix as shown in snapshot table
+pub fn prove_window(start: &str, end: &str) -> Result<()> {
+    let album = db::read_album()?;
+    let find = |prefix: &str| album.iter().position(|s|s.hash.starts_with(prefix));
+    let (start, end) = match (find(start), find(end)) {
+        (Some(start), Some(end)) if start < end => (start, end),
+        (Some(_), Some(_)) => return Err("start snapshot should be taken before end snapshot".into()),
+        _ => return Err("snapshot not found".into()),
+    };
+    let records: Vec<Record> = album[start + 1..=end].iter()
+        .flat_map(|s|s.snapshot_blnd.records.clone()).collect();
+    let market_prices = db::read_price()?;
+    println!("generating window proof of {} records", records.len());
+    let proof = WindowProof::new(&album[start], &album[end], &records, &market_prices);
+    println!("P&L\t\t{}", proof.pnl);
+    db::write_window_proof(proof)?;
+    Ok(())
+}
+
+/// snapshots are specified by hash prefix as shown in snapshot table, all snapshots if not specified
+pub fn prove_hit_rate(positive: u64, range: Option<(&str, &str)>) -> Result<()> {
+    let album = db::read_album()?;
+    if album.is_empty() {
+        println!("{}", "no snapshot. please take snapshot first.");
+        return Ok(())
+    }
+    let find = |prefix: &str| album.iter().position(|s|s.hash.starts_with(prefix));
+    let (start, end) = match range.map(|(start, end)|(find(start), find(end))) {
+        None => (0, album.len() - 1),
+        Some((Some(start), Some(end))) if start <= end => (start, end),
+        Some((Some(_), Some(_))) => return Err("start snapshot should be taken before end snapshot".into()),
+        _ => return Err("snapshot not found".into()),
+    };
+    let snapshots = &album[start..=end];
+    if snapshots.iter().any(|s|s.msg.pnl.is_some()) {
+        println!("{}", "warning: some period P&L is published in snapshot, set pnl_disclosure to keep it private");
+    }
+    let count = snapshots.iter().filter(|s|s.pnl() >= 0.0).count() as u64;
+    if count < positive {
+        return Err(format!("only {} of {} periods are positive", count, snapshots.len()).into())
+    }
+    println!("generating hit rate proof of {} snapshots", snapshots.len());
+    let proof = HitRateProof::new(snapshots, positive);
+    db::write_hit_rate_proof(proof)?;
     Ok(())
 }
 
 pub fn verify(path: &str) -> Result<()> {
     let proof = db::read_proof(path)?;
-    proof.verify_hash()?;
-    proof.verify_sig()?;
-    proof.verify_r1cs()?;
-    Ok(())
+    verify_proof(&proof)
 }
 
 pub fn verify_all() -> Result<()> {
-    let mut count = 0;
+    let proofs = verify_proof_chain()?;
+    if !proofs.is_empty() {
+        println!("Verify all {} proofs OK.", proofs.len());
+    }
+    Ok(())
+}
+
+/// snapshot proofs in proof path, verified one by one and as a snapshot chain
+fn verify_proof_chain() -> Result<Vec<ZKPNLProof>> {
+    let mut proofs: Vec<ZKPNLProof> = vec![];
     for entry in std::fs::read_dir(ZKPNL_CONFIG.proof_path)? {
         if let Some(path) = entry?.path().to_str() {
-            count += 1;
             println!("verify {}", path);
-            verify(path)?;
+            let proof = db::read_proof(path)?;
+            verify_proof(&proof)?;
+            proofs.push(proof);
         }
     }
-    if count == 0 {
+    if proofs.is_empty() {
         println!("no proof file found in path {}", ZKPNL_CONFIG.proof_path);
     } else {
-        println!("Verify all {} proofs OK.", count);
+        ZKPNLProof::verify_chain(&mut proofs)?;
+    }
+    Ok(proofs)
+}
+
+pub fn verify_aggregate(path: &str) -> Result<()> {
+    let proof: AggregateProof = db::read_proof(path)?;
+    proof.verify_hash()?;
+    proof.verify_sig()?;
+    proof.verify_r1cs()?;
+    if !proof.claims.restricted.is_empty() {
+        for snapshot in &proof.snapshots {
+            println!("no position in restricted {} at {}", proof.claims.restricted.join(", "), snapshot.msg.time);
+        }
+    }
+    println!("Verify aggregate proof of {} snapshots OK.", proof.snapshots.len());
+    Ok(())
+}
+
+pub fn verify_hit_rate(path: &str) -> Result<()> {
+    let proof: HitRateProof = db::read_proof(path)?;
+    proof.verify_hash()?;
+    proof.verify_sig()?;
+    proof.verify_r1cs()?;
+    println!("\nFrom\t\t{}\nTo\t\t{}\nPositive\t{} of {}", proof.snapshots.first().unwrap().msg.time,
+             proof.snapshots.last().unwrap().msg.time, proof.positive, proof.snapshots.len());
+    Ok(())
+}
+
+pub fn verify_window(path: &str) -> Result<()> {
+    let proof: WindowProof = db::read_proof(path)?;
+    proof.verify_hash()?;
+    proof.verify_sig()?;
+    proof.verify_r1cs()?;
+    println!("\nFrom\t\t{}\nTo\t\t{}\nP&L\t\t{}", proof.start_snapshot.msg.time, proof.end_snapshot.msg.time, proof.pnl);
+    Ok(())
+}
+
+pub fn verify_opening(path: &str) -> Result<()> {
+    let opening: PortfolioOpening = db::read_proof(path)?;
+    opening.verify_hash()?;
+    opening.verify_sig()?;
+    opening.verify_opening()?;
+    println!("\nTime\t\t{}", opening.snapshot.msg.time);
+    for (symbol, opening) in &opening.openings {
+        println!("{}\t\t{}", symbol, opening.value);
+    }
+    Ok(())
+}
+
+/// count is the sample count asked by auditor, which the response should open in full
+pub fn verify_audit(path: &str, count: usize) -> Result<()> {
+    let response: AuditResponse = db::read_proof(path)?;
+    response.verify_hash()?;
+    response.verify_sig()?;
+    response.verify_opening(count)?;
+    println!("\nChallenge\t{}\nSampled\t\t{} of {}", response.challenge, response.openings.len(), response.hashes.len());
+    for (record, (_, opening)) in response.records.iter().zip(&response.openings) {
+        let msg = &record.msg;
+        println!("{}\t{}\t{}", msg.time, msg.symbol.as_deref().unwrap_or("committed"), opening.value);
+    }
+    Ok(())
+}
+
+pub fn verify_trade(path: &str) -> Result<()> {
+    let proof: TradeInclusion = db::read_proof(path)?;
+    proof.verify_hash()?;
+    proof.verify_sig()?;
+    let msg = &proof.record.msg;
+    println!("\nSnapshot\t{}\nTime\t\t{}\nSymbol\t\t{}\nPrice\t\t{}",
+             proof.snapshot.hash, msg.time, msg.symbol.as_deref().unwrap_or("committed"),
+             msg.price.map_or("committed".to_string(), |p|p.to_string()));
+    Ok(())
+}
+
+pub fn verify_solvency(path: &str) -> Result<()> {
+    let proof: SolvencyProof = db::read_proof(path)?;
+    proof.verify_hash()?;
+    proof.verify_sig()?;
+    proof.verify_r1cs()?;
+    proof.verify_chain(&verify_proof_chain()?)?;
+    println!("\nTime\t\t{}\nLiabilities\t{}", proof.snapshot.msg.time, proof.liabilities);
+    Ok(())
+}
+
+pub fn verify_fund(path: &str) -> Result<()> {
+    let proof: FundProof = db::read_proof(path)?;
+    proof.verify_hash()?;
+    proof.verify_sig()?;
+    proof.verify_opening()?;
+    println!("\nTraders\t\t{}\nFund P&L\t{}", proof.traders.len(), proof.pnl.deintegerize());
+    Ok(())
+}
+
+pub fn show_key() -> Result<()> {
+    match sig::get_pub_key() {
+        Some(_) => println!("{}", sig::get_pub_key_str()),
+        None => println!("{}", "no ed25519 seed found in config"),
+    }
+    Ok(())
+}
+
+fn verify_proof(proof: &ZKPNLProof) -> Result<()> {
+    proof.verify_hash()?;
+    proof.verify_sig()?;
+    proof.verify_r1cs()?;
+    if !proof.claims.restricted.is_empty() {
+        println!("no position in restricted {} at {}", proof.claims.restricted.join(", "), proof.current_snapshot.msg.time);
     }
     Ok(())
 }
@@ -146,7 +484,7 @@ pub fn show_snapshot() -> Result<()> {
     println!("{}", table_row("Hash", "Time", "P&L (USD)", "Log Return"));
     println!("{}", "----------------------------------------------------------------------");
     for snp in album {
-        println!("{}", table_row(&snp.hash[..7], &snp.msg.time.to_rfc2822(), &usd(snp.msg.pnl), &log(snp.msg.log_return)));
+        println!("{}", table_row(&snp.hash[..7], &snp.msg.time.to_rfc2822(), &usd(snp.pnl()), &log(snp.log_return())));
     }
     println!("{}", "");
     Ok(())
@@ -208,4 +546,62 @@ pub fn show_report(range: TimeRange) -> Result<()> {
     println!("Total P&L: {:.1} USD ", total_pnl);
     println!("{}", "");
     Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{self, Book};
+    use super::*;
+
+    const PRICES: &[(&str, f64)] = &[("XBTUSD", 9500.0), ("ETHUSDT", 190.0)];
+
+    /// two hidden trades an hour apart
+    fn book() -> Book {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).hide_price(9000.0).hide_symbol()
+            .wait(60)
+            .trade("ETHUSDT", -50, 200.0, PRICES).hide_price(200.0).hide_symbol();
+        book
+    }
+
+    fn pad(book: &mut Book, padding: usize) -> Result<usize> {
+        let time = book.records.last().unwrap().trade.time + Duration::minutes(1);
+        pad_records(time, &book.album, &mut book.records, &mut book.market_prices, &testing::price_map(PRICES), padding)
+    }
+
+    #[test]
+    fn pad_records_interleaves_dummies_in_a_valid_chain() {
+        let mut book = book();
+        let pads = pad(&mut book, 20).unwrap();
+        assert!(pads > 0);
+        assert_eq!(book.records.len(), 2 + pads);
+        assert!(book.records.windows(2).all(|w|w[0].trade.time < w[1].trade.time));
+        let real: Vec<usize> = (0..book.records.len()).filter(|i|book.records[*i].trade.qty != 0).collect();
+        assert_eq!(real.len(), 2);
+        assert!(real[1] > real[0] + 1, "dummies should be interleaved among real trades");
+        digest::verify_msg_hashes(&book.records);
+        digest::verify_hash_chain_since_genesis(&ZKPNL_CONFIG.transcript, &book.records);
+
+        book.snapshot(PRICES);
+        let proof = book.prove(0, testing::no_claims());
+        assert_eq!(proof.current_snapshot.records().len(), 2 + pads);
+        assert!(testing::verifies(||proof.verify_r1cs()));
+        assert!(testing::verifies(||proof.verify_hash()));
+    }
+
+    #[test]
+    fn pad_records_leaves_period_at_padding_count() {
+        let mut book = book();
+        let hashes: Vec<String> = book.records.iter().map(|r|r.hash.clone()).collect();
+        assert_eq!(pad(&mut book, 2).unwrap(), 0);
+        assert_eq!(book.records.iter().map(|r|r.hash.clone()).collect::<Vec<String>>(), hashes);
+    }
+
+    #[test]
+    fn pad_records_refuses_public_records() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES);
+        assert!(pad(&mut book, 4).is_err());
+        assert_eq!(book.records.len(), 1);
+    }
 }
\ No newline at end of file
@@ -1,21 +1,5 @@
 use linked_hash_map::LinkedHashMap;
-use crate::model::{Record, BlindedRecord, TradeMap, TradeMsgMap, I64TradeMap, MarketPrice};
-
-pub fn get_trade_map(records: &[Record]) -> TradeMap {
-    records.iter().fold(LinkedHashMap::new(), |mut acc, r| {
-        let value = acc.entry(r.trade.symbol.clone()).or_insert(vec![]);
-        value.push(r.trade.clone());
-        acc
-    })
-}
-
-pub fn get_trade_msg_map(records: &[BlindedRecord]) -> TradeMsgMap {
-    records.iter().fold(LinkedHashMap::new(), |mut acc, r| {
-        let value = acc.entry(r.msg.symbol.clone()).or_insert(vec![]);
-        value.push(r.msg.clone());
-        acc
-    })
-}
+use crate::model::{Record, I64TradeMap, MarketPrice};
 
 pub fn get_i64_trade_map(records: &[Record]) -> I64TradeMap {
     records.iter().fold(LinkedHashMap::new(), |mut acc, r| {
@@ -29,4 +13,16 @@ pub fn get_symbols(market_prices: &[MarketPrice]) -> Vec<&str> {
     market_prices.last().map_or(vec![], |mp|{
         mp.market_price.iter().map(|(s, _)|s.as_str()).collect()
     })
+}
+
+/// trade map as of each record, containing trades up to and including that record
+/// symbols traded by each record are in record order and trades of each symbol in trade_map are in the same order
+pub fn get_prefix_trade_maps<LC: Clone>(symbols: &[Vec<&str>], trade_map: &LinkedHashMap<String, Vec<(f64, LC)>>) -> Vec<LinkedHashMap<String, Vec<(f64, LC)>>> {
+    let mut counts: LinkedHashMap<&str, usize> = LinkedHashMap::new();
+    symbols.iter().map(|record_symbols| {
+        for symbol in record_symbols {
+            *counts.entry(symbol).or_insert(0) += 1;
+        }
+        counts.iter().map(|(s, n)| (s.to_string(), trade_map[*s][..*n].to_vec())).collect()
+    }).collect()
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
-pub const PROTOCOL_VERSION: u32 = 2;
+pub const PROTOCOL_VERSION: u32 = 28;
 
 pub const HELP_INFO: &str = r#"
 Zero-knowledge P&L Prover
@@ -9,16 +9,29 @@ USAGE:
     inherit <symbol> <quantity>
     deliver <symbol>
     snapshot
-    prove
-    verify [<proof_file>]
+    prove [aggregate | from <snapshot> to <snapshot>]
+    prove hitrate <count> [from <snapshot> to <snapshot>]
+    prove trade <record>
+    prove solvency <liabilities>
+    prove fund <album_file> <pub_key> [<album_file> <pub_key> ...]
+    verify [[aggregate | window | hitrate | trade | solvency | fund] <proof_file>]
+    open <snapshot> <symbol> [<symbol> ...]
+    verify opening <opening_file>
+    audit <challenge> <count>
+    verify audit <response_file> <count>
     show market (all [save] | <symbol>)
     show report [from <start>] [to (<end> | now)]
     show snapshot
+    show key
     export snapshot
     version
 where <start> and <end> is in format yyyyMMddHHmm
+and <snapshot> is a hash prefix shown in snapshot table, <record> is a hash prefix printed by commit
 "#;
 
 pub const INTEGERIZE_FACTOR: u64 = 1_000_000_000;
 
+/// bit length of range proofs on committed quantities
+pub const RANGE_BITS: usize = 64;
+
 pub const ZKPNL_CONFIG_PATH: &str = "config.toml";
\ No newline at end of file
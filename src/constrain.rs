@@ -1,5 +1,383 @@
-use bulletproofs::r1cs::{ConstraintSystem, LinearCombination};
+use bulletproofs::r1cs::{ConstraintSystem, LinearCombination, R1CSError, Variable};
+use curve25519_dalek::scalar::Scalar;
+use crate::constants::RANGE_BITS;
+use crate::extension::ScalarExt;
 
 pub fn equal<CS: ConstraintSystem, Rhs: Into<LinearCombination>>(cs: &mut CS, lhs: LinearCombination, rhs: Rhs) {
     cs.constrain(lhs - rhs.into());
+}
+
+/// allocate a bit b with a * b = 0 and a = 1 - b, so b is either 0 or 1
+pub fn bit<CS: ConstraintSystem>(cs: &mut CS, assignment: Option<bool>) -> Result<Variable, R1CSError> {
+    let (a, b, o) = cs.allocate_multiplier(assignment.map(|bit| {
+        let bit = bit as u64;
+        ((1 - bit).into(), bit.into())
+    }))?;
+    cs.constrain(o.into());
+    cs.constrain(a + (b - 1u64));
+    Ok(b)
+}
+
+/// enforce 0 <= v < 2^bits by decomposing v into bits
+/// assignment is only known to prover and should be None for verifier
+pub fn range<CS: ConstraintSystem>(cs: &mut CS, mut v: LinearCombination, assignment: Option<u64>, bits: usize) -> Result<(), R1CSError> {
+    let mut exp_2 = Scalar::one();
+    for i in 0..bits {
+        let b = bit(cs, assignment.map(|q| (q >> i) & 1 == 1))?;
+        v = v - b * exp_2;
+        exp_2 = exp_2 + exp_2;
+    }
+    cs.constrain(v);
+    Ok(())
+}
+
+/// enforce -2^(bits-1) <= v < 2^(bits-1) by shifting v into unsigned range
+pub fn signed_range<CS: ConstraintSystem>(cs: &mut CS, v: LinearCombination, assignment: Option<i64>, bits: usize) -> Result<(), R1CSError> {
+    let offset = 1u64 << (bits - 1);
+    let shifted = assignment.map(|a| (a as u64).wrapping_add(offset));
+    range(cs, v + Scalar::from(offset), shifted, bits)
+}
+
+/// enforce |v| <= bound, i.e. both bound - v and bound + v are non-negative
+pub fn abs_at_most<CS: ConstraintSystem>(cs: &mut CS, v: LinearCombination, assignment: Option<i64>, bound: u64) -> Result<(), R1CSError> {
+    if bound > i64::MAX as u64 {
+        return Err(R1CSError::GadgetError { description: format!("bound {} is beyond signed 64-bit range", bound) })
+    }
+    let upper = assignment.map(|a| (bound as i128 - a as i128) as u64);
+    let lower = assignment.map(|a| (bound as i128 + a as i128) as u64);
+    range(cs, LinearCombination::from(bound) - v.clone(), upper, RANGE_BITS)?;
+    range(cs, v + bound, lower, RANGE_BITS)
+}
+
+/// enforce lower <= v < upper, an absent bound is not constrained
+pub fn within<CS: ConstraintSystem>(cs: &mut CS, v: LinearCombination, assignment: Option<i64>,
+                                    lower: Option<i64>, upper: Option<i64>) -> Result<(), R1CSError> {
+    if let Some(lower) = lower {
+        let diff = assignment.map(|a| (a as i128 - lower as i128) as u64);
+        range(cs, v.clone() - Scalar::from_i64(lower), diff, RANGE_BITS)?;
+    }
+    if let Some(upper) = upper {
+        let diff = assignment.map(|a| (upper as i128 - 1 - a as i128) as u64);
+        range(cs, LinearCombination::from(Scalar::from_i64(upper) - Scalar::one()) - v, diff, RANGE_BITS)?;
+    }
+    Ok(())
+}
+
+/// enforce v <= bound
+pub fn at_most<CS: ConstraintSystem>(cs: &mut CS, v: LinearCombination, assignment: Option<i64>, bound: i64) -> Result<(), R1CSError> {
+    within(cs, v, assignment, None, Some(bound + 1))
+}
+
+/// absolute value of v by allocating a sign bit s so that |v| = v - 2sv
+/// range proof on the result rules out a wrong sign bit
+pub fn abs<CS: ConstraintSystem>(cs: &mut CS, v: LinearCombination, assignment: Option<i64>) -> Result<LinearCombination, R1CSError> {
+    let s = bit(cs, assignment.map(|a| a < 0))?;
+    let (_, _, sv) = cs.multiply(s.into(), v.clone());
+    let abs = v - sv * Scalar::from(2u64);
+    range(cs, abs.clone(), assignment.map(|a| (a as i128).abs() as u64), RANGE_BITS)?;
+    Ok(abs)
+}
+
+/// enforce m = max(a, b) by m - a >= 0, m - b >= 0 and (m - a) * (m - b) = 0
+pub fn max<CS: ConstraintSystem>(cs: &mut CS, m: LinearCombination, a: LinearCombination, b: LinearCombination,
+                                 assignments: Option<(i64, i64)>) -> Result<(), R1CSError> {
+    let (_, _, o) = cs.multiply(m.clone() - a.clone(), m.clone() - b.clone());
+    cs.constrain(o.into());
+    range(cs, m.clone() - a, assignments.map(|(a, b)| (a.max(b) as i128 - a as i128) as u64), RANGE_BITS)?;
+    range(cs, m - b, assignments.map(|(a, b)| (a.max(b) as i128 - b as i128) as u64), RANGE_BITS)
+}
+
+/// enforce v to be one of candidates by allocating one-hot bits, which are returned in candidate order
+/// assignment is the index of v in candidates
+pub fn one_hot<CS: ConstraintSystem>(cs: &mut CS, v: LinearCombination, candidates: &[Scalar], assignment: Option<usize>) -> Result<Vec<LinearCombination>, R1CSError> {
+    let mut bits = vec![];
+    let (mut sum, mut selected) = (LinearCombination::default(), LinearCombination::default());
+    for (i, candidate) in candidates.iter().enumerate() {
+        let b = bit(cs, assignment.map(|index| i == index))?;
+        sum = sum + b;
+        selected = selected + b * *candidate;
+        bits.push(b.into());
+    }
+    cs.constrain(sum - 1u64);
+    cs.constrain(selected - v);
+    Ok(bits)
+}
+
+/// enforce v = round(n / d) for public v, i.e. |n - v * d| <= d / 2, by range proofs on d - 2(n - v * d) and d + 2(n - v * d)
+pub fn rounded_quotient<CS: ConstraintSystem>(cs: &mut CS, n: LinearCombination, d: LinearCombination,
+                                              assignments: Option<(i128, i128)>, v: i64) -> Result<(), R1CSError> {
+    let diff = n - d.clone() * Scalar::from_i64(v);
+    let upper = assignments.map(|(n, d)| (d - 2 * (n - d * v as i128)) as u64);
+    let lower = assignments.map(|(n, d)| (d + 2 * (n - d * v as i128)) as u64);
+    range(cs, d.clone() - diff.clone() * Scalar::from(2u64), upper, RANGE_BITS)?;
+    range(cs, d + diff * Scalar::from(2u64), lower, RANGE_BITS)
+}
+
+/// enforce fee = floor(rate * max(0, v) / unit) by allocating m = max(0, v)
+/// and bounding the remainder 0 <= rate * m - unit * fee < unit
+pub fn fee<CS: ConstraintSystem>(cs: &mut CS, v: LinearCombination, assignment: Option<i64>,
+                                 rate: i64, unit: i64, fee: i64) -> Result<(), R1CSError> {
+    let m = cs.allocate(assignment.map(|a| Scalar::from_i64(a.max(0))))?;
+    max(cs, m.into(), LinearCombination::default(), v, assignment.map(|a| (0, a)))?;
+    let remainder = LinearCombination::from(m) * Scalar::from_i64(rate) - Scalar::from_i64(unit) * Scalar::from_i64(fee);
+    let diff = assignment.map(|a| (a.max(0) as i128 * rate as i128 - unit as i128 * fee as i128) as i64);
+    within(cs, remainder, diff, Some(0), Some(unit))
+}
+
+/// enforce at least count of values to be non-negative by allocating an indicator bit for each value,
+/// range proof on bit * v rules out a set bit on a negative value
+pub fn at_least_non_negative<CS: ConstraintSystem>(cs: &mut CS, values: &[(LinearCombination, Option<i64>)], count: u64) -> Result<(), R1CSError> {
+    if values.is_empty() {
+        return Err(R1CSError::GadgetError { description: "no value to count non-negative ones".to_string() })
+    }
+    let mut sum = LinearCombination::default();
+    for (v, assignment) in values {
+        let b = bit(cs, assignment.map(|a| a >= 0))?;
+        let (_, _, selected) = cs.multiply(b.into(), v.clone());
+        range(cs, selected.into(), assignment.map(|a| a.max(0) as u64), RANGE_BITS)?;
+        sum = sum + b;
+    }
+    let total = values.iter().map(|(_, a)|a.map(|a| (a >= 0) as u64)).sum::<Option<u64>>();
+    range(cs, sum - count, total.map(|t| t.wrapping_sub(count)), RANGE_BITS)
+}
+
+#[cfg(test)]
+mod tests {
+    use bulletproofs::r1cs::{Prover, Verifier};
+    use bulletproofs::PedersenGens;
+    use merlin::Transcript;
+    use crate::extension::make_bp_gens;
+    use super::*;
+
+    /// commit values and build the same gadget on both sides, true if the proof verifies
+    pub fn verifies<P, V>(values: &[i64], prove: P, verify: V) -> bool
+        where P: FnOnce(&mut Prover, &[LinearCombination]) -> Result<(), R1CSError>,
+              V: FnOnce(&mut Verifier, &[LinearCombination]) -> Result<(), R1CSError> {
+        let pc_gens = PedersenGens::default();
+        let mut transcript = Transcript::new(b"zkpnl test");
+        let mut prover = Prover::new(&pc_gens, &mut transcript);
+        let (cmts, vars): (Vec<_>, Vec<LinearCombination>) = values.iter().map(|v|{
+            let (cmt, var) = prover.commit(Scalar::from_i64(*v), Scalar::random(&mut rand::thread_rng()));
+            (cmt, var.into())
+        }).unzip();
+        if prove(&mut prover, &vars).is_err() {
+            return false
+        }
+        let bp_gens = make_bp_gens(&prover);
+        let proof = match prover.prove(&bp_gens) {
+            Ok(proof) => proof,
+            Err(_) => return false,
+        };
+        let mut transcript = Transcript::new(b"zkpnl test");
+        let mut verifier = Verifier::new(&mut transcript);
+        let vars: Vec<LinearCombination> = cmts.iter().map(|c|verifier.commit(*c).into()).collect();
+        verify(&mut verifier, &vars).is_ok() && verifier.verify(&proof, &pc_gens, &bp_gens).is_ok()
+    }
+
+    fn range_verifies(v: i64, bits: usize) -> bool {
+        verifies(&[v], |cs, vars|range(cs, vars[0].clone(), Some(v as u64), bits),
+                 |cs, vars|range(cs, vars[0].clone(), None, bits))
+    }
+
+    fn signed_range_verifies(v: i64, bits: usize) -> bool {
+        verifies(&[v], |cs, vars|signed_range(cs, vars[0].clone(), Some(v), bits),
+                 |cs, vars|signed_range(cs, vars[0].clone(), None, bits))
+    }
+
+    #[test]
+    fn bit_is_zero_or_one() {
+        for b in &[false, true] {
+            assert!(verifies(&[*b as i64], |cs, vars|{
+                let bit = bit(cs, Some(*b))?;
+                equal(cs, vars[0].clone(), bit);
+                Ok(())
+            }, |cs, vars|{
+                let bit = bit(cs, None)?;
+                equal(cs, vars[0].clone(), bit);
+                Ok(())
+            }));
+        }
+    }
+
+    #[test]
+    fn range_accepts_values_within_bits() {
+        assert!(range_verifies(0, 8));
+        assert!(range_verifies(255, 8));
+        assert!(range_verifies(i64::MAX, RANGE_BITS));
+    }
+
+    #[test]
+    fn range_rejects_values_beyond_bits() {
+        assert!(!range_verifies(256, 8));
+        assert!(!range_verifies(-1, 8));
+    }
+
+    #[test]
+    fn signed_range_accepts_both_ends() {
+        assert!(signed_range_verifies(-128, 8));
+        assert!(signed_range_verifies(127, 8));
+        assert!(signed_range_verifies(i64::MIN, RANGE_BITS));
+        assert!(signed_range_verifies(i64::MAX, RANGE_BITS));
+    }
+
+    fn abs_at_most_verifies(v: i64, bound: u64) -> bool {
+        verifies(&[v], |cs, vars|abs_at_most(cs, vars[0].clone(), Some(v), bound),
+                 |cs, vars|abs_at_most(cs, vars[0].clone(), None, bound))
+    }
+
+    fn within_verifies(v: i64, lower: Option<i64>, upper: Option<i64>) -> bool {
+        verifies(&[v], |cs, vars|within(cs, vars[0].clone(), Some(v), lower, upper),
+                 |cs, vars|within(cs, vars[0].clone(), None, lower, upper))
+    }
+
+    #[test]
+    fn within_includes_lower_and_excludes_upper() {
+        assert!(within_verifies(-5, Some(-5), Some(5)));
+        assert!(within_verifies(4, Some(-5), Some(5)));
+        assert!(!within_verifies(5, Some(-5), Some(5)));
+        assert!(!within_verifies(-6, Some(-5), Some(5)));
+    }
+
+    #[test]
+    fn within_leaves_absent_bound_open() {
+        assert!(within_verifies(i64::MIN, None, Some(0)));
+        assert!(within_verifies(i64::MAX, Some(0), None));
+        assert!(within_verifies(-1, None, None));
+        assert!(!within_verifies(0, None, Some(0)));
+    }
+
+    /// whether |v| is proven equal to w
+    fn abs_verifies(v: i64, w: i64) -> bool {
+        verifies(&[v, w], |cs, vars|{
+            let abs = abs(cs, vars[0].clone(), Some(v))?;
+            equal(cs, abs, vars[1].clone());
+            Ok(())
+        }, |cs, vars|{
+            let abs = abs(cs, vars[0].clone(), None)?;
+            equal(cs, abs, vars[1].clone());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn abs_flips_negative_values_only() {
+        assert!(abs_verifies(7, 7));
+        assert!(abs_verifies(-7, 7));
+        assert!(abs_verifies(0, 0));
+        assert!(abs_verifies(i64::MIN + 1, i64::MAX));
+    }
+
+    #[test]
+    fn abs_rejects_wrong_sign() {
+        assert!(!abs_verifies(-7, -7));
+        assert!(!abs_verifies(7, -7));
+    }
+
+    fn max_verifies(m: i64, a: i64, b: i64) -> bool {
+        verifies(&[m, a, b], |cs, vars|max(cs, vars[0].clone(), vars[1].clone(), vars[2].clone(), Some((a, b))),
+                 |cs, vars|max(cs, vars[0].clone(), vars[1].clone(), vars[2].clone(), None))
+    }
+
+    #[test]
+    fn max_accepts_larger_operand() {
+        assert!(max_verifies(3, 3, -2));
+        assert!(max_verifies(3, -2, 3));
+        assert!(max_verifies(0, 0, 0));
+    }
+
+    #[test]
+    fn max_rejects_other_values() {
+        assert!(!max_verifies(-2, 3, -2));
+        assert!(!max_verifies(4, 3, -2));
+    }
+
+    /// whether v is proven to be the candidate at index
+    fn one_hot_verifies(v: i64, candidates: &[i64], index: usize) -> bool {
+        let candidates: Vec<Scalar> = candidates.iter().map(|c|Scalar::from_i64(*c)).collect();
+        verifies(&[v], |cs, vars|one_hot(cs, vars[0].clone(), &candidates, Some(index)).map(|_|()),
+                 |cs, vars|one_hot(cs, vars[0].clone(), &candidates, None).map(|_|()))
+    }
+
+    #[test]
+    fn one_hot_selects_candidate() {
+        assert!(one_hot_verifies(-3, &[5, -3, 7], 1));
+        assert!(one_hot_verifies(5, &[5], 0));
+    }
+
+    #[test]
+    fn one_hot_rejects_value_outside_candidates() {
+        assert!(!one_hot_verifies(4, &[5, -3, 7], 0));
+        assert!(!one_hot_verifies(5, &[5, -3, 7], 2));
+    }
+
+    fn at_least_non_negative_verifies(values: &[i64], count: u64) -> bool {
+        let assigned = |vars: &[LinearCombination]| -> Vec<(LinearCombination, Option<i64>)> {
+            vars.iter().cloned().zip(values.iter().map(|v|Some(*v))).collect()
+        };
+        verifies(values, |cs, vars|at_least_non_negative(cs, &assigned(vars), count),
+                 |cs, vars|at_least_non_negative(cs, &vars.iter().map(|v|(v.clone(), None)).collect::<Vec<_>>(), count))
+    }
+
+    #[test]
+    fn at_least_non_negative_counts_zero_as_non_negative() {
+        assert!(at_least_non_negative_verifies(&[5, -3, 0], 2));
+        assert!(at_least_non_negative_verifies(&[-5, -3], 0));
+    }
+
+    #[test]
+    fn at_least_non_negative_rejects_count_above_non_negative_values() {
+        assert!(!at_least_non_negative_verifies(&[5, -3, 0], 3));
+        assert!(!at_least_non_negative_verifies(&[-1], 1));
+    }
+
+    #[test]
+    fn at_least_non_negative_rejects_empty_values() {
+        assert!(!verifies(&[], |cs, _|at_least_non_negative(cs, &[], 0), |cs, _|at_least_non_negative(cs, &[], 0)));
+    }
+
+    fn fee_verifies(v: i64, rate: i64, unit: i64, fee_value: i64) -> bool {
+        verifies(&[v], |cs, vars|fee(cs, vars[0].clone(), Some(v), rate, unit, fee_value),
+                 |cs, vars|fee(cs, vars[0].clone(), None, rate, unit, fee_value))
+    }
+
+    #[test]
+    fn fee_rounds_down_on_positive_excess() {
+        assert!(fee_verifies(1_000, 200, 1_000, 200));
+        assert!(fee_verifies(999, 200, 1_000, 199));
+        assert!(!fee_verifies(999, 200, 1_000, 200));
+        assert!(!fee_verifies(999, 200, 1_000, 198));
+    }
+
+    #[test]
+    fn fee_is_zero_below_mark() {
+        assert!(fee_verifies(-1_000, 200, 1_000, 0));
+        assert!(fee_verifies(0, 200, 1_000, 0));
+        assert!(!fee_verifies(-1_000, 200, 1_000, -200));
+    }
+
+    #[test]
+    fn abs_at_most_accepts_both_signs_within_bound() {
+        assert!(abs_at_most_verifies(100, 100));
+        assert!(abs_at_most_verifies(-100, 100));
+        assert!(abs_at_most_verifies(0, 0));
+        assert!(abs_at_most_verifies(i64::MIN + 1, i64::MAX as u64));
+    }
+
+    #[test]
+    fn abs_at_most_rejects_both_signs_beyond_bound() {
+        assert!(!abs_at_most_verifies(101, 100));
+        assert!(!abs_at_most_verifies(-101, 100));
+    }
+
+    #[test]
+    fn abs_at_most_rejects_bound_beyond_signed_range() {
+        assert!(!abs_at_most_verifies(0, i64::MAX as u64 + 1));
+        assert!(!abs_at_most_verifies(0, u64::MAX));
+    }
+
+    #[test]
+    fn signed_range_rejects_wrapped_values() {
+        assert!(!signed_range_verifies(128, 8));
+        assert!(!signed_range_verifies(-129, 8));
+    }
 }
\ No newline at end of file
@@ -64,6 +64,31 @@ pub fn calc_total_pnl<LC, S>(trade_map: &LinkedHashMap<String, Vec<(f64, LC)>>,
     }).fold(LC::default(), |acc, lc|acc + lc)
 }
 
+/// performance fee on the excess of equity over high-water mark, rounded down to integerized unit
+pub fn calc_fee(rate: f64, excess: i64) -> i64 {
+    (i64::integerize(rate) as i128 * excess.max(0) as i128 / INTEGERIZE_FACTOR as i128) as i64
+}
+
+/// simple return of pnl on capital in integerized unit, rounded to nearest
+pub fn calc_return(pnl: i64, capital: i64) -> i64 {
+    round_div(pnl as i128 * INTEGERIZE_FACTOR as i128, capital as i128)
+}
+
+/// price in cents, the unit equity is divided by when denominated in BTC
+pub fn calc_cents(price: f64) -> i64 {
+    (price * 100.0).round() as i64
+}
+
+/// change of equity in BTC from equity0 at cents0 to equity1 at cents1 in integerized unit, rounded to nearest
+pub fn calc_btc_pnl(equity0: i64, cents0: i64, equity1: i64, cents1: i64) -> i64 {
+    let n = (equity1 as i128 * cents0 as i128 - equity0 as i128 * cents1 as i128) * 100;
+    round_div(n, cents0 as i128 * cents1 as i128)
+}
+
+fn round_div(n: i128, d: i128) -> i64 {
+    (2 * n + d).div_euclid(2 * d) as i64
+}
+
 /// P&L can be described as cash balance (with an initial balance of zero)
 /// plus market value of current position
 fn calc_pnl<LC, S>(trades: &[(f64, LC)], market_price: f64) -> LC
@@ -1,15 +1,17 @@
 use std::collections::HashMap;
 use std::io::ErrorKind;
 use std::fs::{read_to_string, write};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use serde_json::{from_str, to_string_pretty, Value};
 use csv::Writer;
 use crate::digest::{verify_msg_hashes, verify_hash_chain_since_genesis};
 use crate::model::{MarketPrice, Record, Snapshot};
-use crate::proof::ZKPNLProof;
+use crate::proof::{AggregateProof, AuditResponse, FundProof, HitRateProof, PortfolioOpening, SolvencyProof, TradeInclusion, WindowProof, ZKPNLProof};
 use crate::sig::verify_sig;
 use crate::{ZKPNL_CONFIG, Result};
 use crate::constants::PROTOCOL_VERSION;
-use crate::report::SNPReport;
+use crate::report::{FeeReport, SNPReport};
 
 pub fn read_price() -> Result<Vec<MarketPrice>> {
     println!("{}", "read price file");
@@ -41,14 +43,23 @@ pub fn read_album() -> Result<Vec<Snapshot>> {
     Ok(album)
 }
 
-pub fn read_proof(path: &str) -> Result<ZKPNLProof> {
+/// album of another trader, whose signatures are verified with the trader's key in fund proof
+pub fn read_album_from(path: &str) -> Result<Vec<Snapshot>> {
+    println!("read album {}", path);
+    let string = read_to_string(path)?;
+    let album: Vec<Snapshot> = from_str(&string)?;
+    verify_msg_hashes(&album);
+    Ok(album)
+}
+
+pub fn read_proof<P: DeserializeOwned>(path: &str) -> Result<P> {
     println!("{}", "read proof");
     let string: String = read_to_string(path)?;
     println!("{}", "parse proof");
     let map: HashMap<String, Value> = from_str(&string)?;
     if let Some(v) = map.get("protocol_version").and_then(|v|v.as_u64()) {
         if v == PROTOCOL_VERSION as u64 {
-            let proof: ZKPNLProof = from_str(&string)?;
+            let proof: P = from_str(&string)?;
             Ok(proof)
         } else {
             panic!("proof file version incompatible")
@@ -59,40 +70,81 @@ pub fn read_proof(path: &str) -> Result<ZKPNLProof> {
 }
 
 pub fn write_price(market_prices: Vec<MarketPrice>) -> Result<()> {
-    println!("{}", "serialize price");
-    let market_price_json = to_string_pretty(&market_prices)?;
-    println!("{}", "write price");
-    write(ZKPNL_CONFIG.price_path, market_price_json)?;
-    Ok(())
+    write_json("price", &market_prices, ZKPNL_CONFIG.price_path.to_string())
 }
 
 pub fn write_record(records: Vec<Record>) -> Result<()> {
-    println!("{}", "serialize record");
-    let record_json = to_string_pretty(&records)?;
-    println!("{}", "write record");
-    write(ZKPNL_CONFIG.record_path, record_json)?;
-    Ok(())
+    write_json("record", &records, ZKPNL_CONFIG.record_path.to_string())
 }
 
 pub fn write_album(album: Vec<Snapshot>) -> Result<()> {
-    println!("{}", "serialize album");
-    let album_json = to_string_pretty(&album)?;
-    println!("{}", "write album");
-    write(ZKPNL_CONFIG.album_path, album_json)?;
-    Ok(())
+    write_json("album", &album, ZKPNL_CONFIG.album_path.to_string())
 }
 
 pub fn write_proof(proof: ZKPNLProof) -> Result<()> {
-    println!("{}", "serialize proof");
-    let proof_json = to_string_pretty(&proof)?;
-    let start = proof.previous_snapshot.map(|s|s.msg.time)
-        .or(proof.current_snapshot.msg.records.first().map(|r|r.msg.time))
+    let start = proof.previous_snapshot.as_ref().map(|s|s.msg.time)
+        .or(proof.current_snapshot.records().first().map(|r|r.msg.time))
         .map(|t|t.format("%F-%H%M%S").to_string())
         .unwrap_or("initial".to_string());
     let end = proof.current_snapshot.msg.time.format("%F-%H%M%S").to_string();
     let path = format!("{}proof_from_{}_to_{}.json", ZKPNL_CONFIG.proof_path, start, end);
-    println!("write proof to path: {}", path);
-    write(path, proof_json)?;
+    write_json("proof", &proof, path)
+}
+
+pub fn write_aggregate_proof(proof: AggregateProof) -> Result<()> {
+    let start = proof.snapshots.first().unwrap().msg.time.format("%F-%H%M%S").to_string();
+    let end = proof.snapshots.last().unwrap().msg.time.format("%F-%H%M%S").to_string();
+    let path = format!("data/aggregate_proof_from_{}_to_{}.json", start, end);
+    write_json("proof", &proof, path)
+}
+
+pub fn write_window_proof(proof: WindowProof) -> Result<()> {
+    let start = proof.start_snapshot.msg.time.format("%F-%H%M%S").to_string();
+    let end = proof.end_snapshot.msg.time.format("%F-%H%M%S").to_string();
+    let path = format!("data/window_proof_from_{}_to_{}.json", start, end);
+    write_json("proof", &proof, path)
+}
+
+pub fn write_solvency_proof(proof: SolvencyProof) -> Result<()> {
+    let time = proof.snapshot.msg.time.format("%F-%H%M%S").to_string();
+    let path = format!("data/solvency_proof_at_{}.json", time);
+    write_json("proof", &proof, path)
+}
+
+pub fn write_hit_rate_proof(proof: HitRateProof) -> Result<()> {
+    let start = proof.snapshots.first().unwrap().msg.time.format("%F-%H%M%S").to_string();
+    let end = proof.snapshots.last().unwrap().msg.time.format("%F-%H%M%S").to_string();
+    let path = format!("data/hit_rate_proof_from_{}_to_{}.json", start, end);
+    write_json("proof", &proof, path)
+}
+
+pub fn write_opening(opening: PortfolioOpening) -> Result<()> {
+    let time = opening.snapshot.msg.time.format("%F-%H%M%S").to_string();
+    let path = format!("data/opening_at_{}.json", time);
+    write_json("opening", &opening, path)
+}
+
+pub fn write_trade_inclusion(proof: TradeInclusion) -> Result<()> {
+    let path = format!("data/trade_inclusion_of_{}.json", &proof.record.hash[..8]);
+    write_json("proof", &proof, path)
+}
+
+pub fn write_audit_response(response: AuditResponse) -> Result<()> {
+    let path = format!("data/audit_response_to_{}.json", &response.challenge[..response.challenge.len().min(8)]);
+    write_json("audit response", &response, path)
+}
+
+pub fn write_fund_proof(proof: FundProof) -> Result<()> {
+    let end = proof.traders.iter().map(|t|t.snapshot.msg.time).max().unwrap().format("%F-%H%M%S").to_string();
+    let path = format!("data/fund_proof_of_{}_traders_to_{}.json", proof.traders.len(), end);
+    write_json("proof", &proof, path)
+}
+
+fn write_json<T: Serialize>(name: &str, value: &T, path: String) -> Result<()> {
+    println!("serialize {}", name);
+    let json = to_string_pretty(value)?;
+    println!("write {} to path: {}", name, path);
+    write(path, json)?;
     Ok(())
 }
 
@@ -114,6 +166,24 @@ pub fn write_snp_report(reports: Vec<SNPReport>) -> Result<()> {
     Ok(())
 }
 
+pub fn write_fee_report(reports: Vec<FeeReport>) -> Result<()> {
+    if reports.is_empty() {
+        println!("{}", "no performance fee to export");
+        return Ok(())
+    }
+    let start = reports.first().unwrap().time.format("%F-%H%M%S").to_string();
+    let end = reports.last().unwrap().time.format("%F-%H%M%S").to_string();
+    let path = format!("data/fee_statement_from_{}_to_{}.csv", start, end);
+    let mut wtr = Writer::from_path(path)?;
+    println!("{}", "exporting fee statement");
+    for r in reports {
+        wtr.serialize(r)?;
+    }
+    wtr.flush()?;
+    println!("{}", "completed");
+    Ok(())
+}
+
 fn read_or_write_default(default: &str, path: &str) -> String {
     match read_to_string(path) {
         Ok(string) => string,
@@ -1,6 +1,14 @@
 use crypto::digest::Digest;
 use crypto::sha2::Sha256;
-use crate::model::Verifiable;
+use serde::{Deserialize, Serialize};
+use crate::model::{PriceMap, Verifiable};
+
+/// sibling on the path from a leaf to merkle root
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MerkleNode {
+    pub hash: String,
+    pub is_left: bool,
+}
 
 pub fn sha256(str: &str) -> String {
     let mut hasher = Sha256::new();
@@ -8,6 +16,87 @@ pub fn sha256(str: &str) -> String {
     hasher.result_str()
 }
 
+/// hash of a price map as published in proofs
+pub fn price_map_hash(price_map: &PriceMap) -> String {
+    sha256(&serde_json::to_string(price_map).unwrap())
+}
+
+/// integer standing for a symbol in commitments, taken from its hash
+pub fn symbol_id(symbol: &str) -> i64 {
+    i64::from_str_radix(&sha256(symbol)[..15], 16).unwrap()
+}
+
+/// distinct indices below total picked by challenge, all indices if count is no less than total
+pub fn sample(challenge: &str, count: usize, total: usize) -> Vec<usize> {
+    let mut indices = vec![];
+    let mut counter = 0u64;
+    while indices.len() < count.min(total) {
+        let hash = sha256(&format!("{}{}", challenge, counter));
+        let index = (u64::from_str_radix(&hash[..15], 16).unwrap() % total as u64) as usize;
+        if !indices.contains(&index) { indices.push(index) }
+        counter += 1;
+    }
+    indices
+}
+
+/// merkle root of hashes, the last node of an odd level is promoted to next level as is
+pub fn merkle_root(hashes: &[String]) -> String {
+    if hashes.is_empty() {
+        return sha256("")
+    }
+    let mut level: Vec<String> = hashes.iter().map(|h|merkle_leaf(h)).collect();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level.remove(0)
+}
+
+/// siblings from the leaf at index up to merkle root, a promoted node has no sibling on its level
+pub fn merkle_path(hashes: &[String], mut index: usize) -> Vec<MerkleNode> {
+    let mut path = vec![];
+    let mut level: Vec<String> = hashes.iter().map(|h|merkle_leaf(h)).collect();
+    while level.len() > 1 {
+        let sibling = if index % 2 == 0 { index + 1 } else { index - 1 };
+        if sibling < level.len() {
+            path.push(MerkleNode { hash: level[sibling].clone(), is_left: sibling < index });
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    path
+}
+
+/// leaves and internal nodes are tagged apart, so an internal node can't pass as a leaf
+fn merkle_leaf(hash: &str) -> String {
+    sha256(&format!("leaf:{}", hash))
+}
+
+fn merkle_node(left: &str, right: &str) -> String {
+    sha256(&format!("node:{}{}", left, right))
+}
+
+fn next_level(level: &[String]) -> Vec<String> {
+    level.chunks(2).map(|pair|{
+        match pair.get(1) {
+            Some(right) => merkle_node(&pair[0], right),
+            None => pair[0].clone(),
+        }
+    }).collect()
+}
+
+pub fn verify_merkle_path(leaf: &str, path: &[MerkleNode], root: &str) {
+    let computed = path.iter().fold(merkle_leaf(leaf), |acc, node|{
+        if node.is_left {
+            merkle_node(&node.hash, &acc)
+        } else {
+            merkle_node(&acc, &node.hash)
+        }
+    });
+    if computed != root {
+        panic!("verify merkle path failed at {}", leaf)
+    }
+}
+
 pub fn verify_msg_hashes<V: Verifiable>(verifiables: &[V]) {
     println!("{}", "verify message hash");
     for v in verifiables {
@@ -22,6 +111,7 @@ pub fn verify_msg_hash<V: Verifiable>(verifiable: &V) {
 }
 
 pub fn verify_hash_chain<V: Verifiable>(verifiables: &[V]) {
+    verify_time_order(verifiables);
     let hashes: Vec<String> = verifiables.iter().skip(1)
         .map(|r|{
             r.prev_hash().replacen("\u{200b}", "", 1)
@@ -37,6 +127,7 @@ pub fn verify_hash_chain<V: Verifiable>(verifiables: &[V]) {
 
 pub fn verify_hash_chain_since_genesis<V: Verifiable>(genesis_text: &str, verifiables: &[V]) {
     println!("{}", "verify hash chain");
+    verify_time_order(verifiables);
     let hashes: Vec<String> = verifiables.iter()
         .map(|r|{
             r.prev_hash().replacen("\u{200b}", "", 1)
@@ -49,4 +140,104 @@ pub fn verify_hash_chain_since_genesis<V: Verifiable>(genesis_text: &str, verifi
             panic!("verify hash chain failed at {}", h)
         }
     };
+}
+
+/// published time may be coarsened so consecutive messages can share the same time
+fn verify_time_order<V: Verifiable>(verifiables: &[V]) {
+    for (p, v) in verifiables.iter().zip(verifiables.iter().skip(1)) {
+        if v.time() < p.time() {
+            panic!("verify time order failed at {}", v.hash())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::Record;
+    use crate::testing::Book;
+    use crate::time::{self, Granularity};
+    use crate::ZKPNL_CONFIG;
+    use super::*;
+
+    const PRICES: &[(&str, f64)] = &[("XBTUSD", 9500.0), ("ETHUSDT", 190.0)];
+
+    /// records published at the hour, linked again after coarsening
+    fn coarsened_records() -> Vec<Record> {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, PRICES).trade("ETHUSDT", -50, 200.0, PRICES).trade("XBTUSD", -20, 9100.0, PRICES);
+        let mut records: Vec<Record> = vec![];
+        for mut record in book.records {
+            record.msg.time = time::coarsen(record.msg.time, Granularity::Hour);
+            let prev_hash = records.last().map_or_else(||sha256(ZKPNL_CONFIG.transcript), |r|r.hash.clone());
+            record.relink(&prev_hash).unwrap();
+            records.push(record);
+        }
+        records
+    }
+
+    #[test]
+    fn hash_chain_accepts_equal_coarsened_times() {
+        let records = coarsened_records();
+        assert!(records.windows(2).all(|w|w[0].msg.time == w[1].msg.time));
+        verify_msg_hashes(&records);
+        verify_hash_chain(&records);
+        verify_hash_chain_since_genesis(ZKPNL_CONFIG.transcript, &records);
+    }
+
+    #[test]
+    #[should_panic(expected = "verify time order failed")]
+    fn hash_chain_rejects_time_going_back() {
+        let mut records = coarsened_records();
+        records[2].msg.time -= chrono::Duration::hours(1);
+        verify_hash_chain(&records);
+    }
+
+    #[test]
+    #[should_panic(expected = "verify hash chain failed")]
+    fn hash_chain_rejects_tampered_message() {
+        let mut records = coarsened_records();
+        records[1].msg.qty = records[0].msg.qty.clone();
+        verify_hash_chain(&records);
+    }
+
+    fn hashes(n: usize) -> Vec<String> {
+        (0..n).map(|i|sha256(&i.to_string())).collect()
+    }
+
+    #[test]
+    fn merkle_path_verifies_every_leaf() {
+        for n in 1..8 {
+            let hashes = hashes(n);
+            let root = merkle_root(&hashes);
+            for (i, hash) in hashes.iter().enumerate() {
+                verify_merkle_path(hash, &merkle_path(&hashes, i), &root);
+            }
+        }
+    }
+
+    #[test]
+    fn merkle_root_promotes_odd_node() {
+        let hashes = hashes(3);
+        let left = merkle_node(&merkle_leaf(&hashes[0]), &merkle_leaf(&hashes[1]));
+        assert_eq!(merkle_root(&hashes), merkle_node(&left, &merkle_leaf(&hashes[2])));
+        let mut duplicated = hashes.clone();
+        duplicated.push(hashes[2].clone());
+        assert_ne!(merkle_root(&hashes), merkle_root(&duplicated));
+    }
+
+    #[test]
+    #[should_panic(expected = "verify merkle path failed")]
+    fn merkle_path_rejects_internal_node_as_leaf() {
+        let hashes = hashes(4);
+        let node = merkle_node(&merkle_leaf(&hashes[0]), &merkle_leaf(&hashes[1]));
+        let path = merkle_path(&hashes, 0)[1..].to_vec();
+        verify_merkle_path(&node, &path, &merkle_root(&hashes));
+    }
+
+    #[test]
+    #[should_panic(expected = "verify merkle path failed")]
+    fn merkle_path_rejects_other_leaf() {
+        let hashes = hashes(5);
+        verify_merkle_path(&hashes[1], &merkle_path(&hashes, 4), &merkle_root(&hashes));
+    }
 }
\ No newline at end of file
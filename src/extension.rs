@@ -1,10 +1,15 @@
 use linked_hash_map::LinkedHashMap;
-use bulletproofs::r1cs::{LinearCombination, Prover, Verifier};
-use bulletproofs::PedersenGens;
+use bulletproofs::r1cs::{ConstraintSystem, LinearCombination, Prover, Verifier};
+use bulletproofs::{BulletproofGens, PedersenGens};
 use curve25519_dalek::scalar::Scalar;
-use curve25519_dalek::ristretto::CompressedRistretto;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::traits::Identity;
 use merlin::Transcript;
-use crate::model::{TradeMap, TradeMsgMap, LCTradeMap, PortMap, PortCmtMap, PortBlndMap, LCPortMap};
+use crate::{constrain, digest};
+use crate::constants::RANGE_BITS;
+use crate::core::Integerize;
+use crate::model::{PortMap, PortCmtMap, PortBlndMap, LCPortMap, Opening};
+use crate::model::{Record, BlindedRecord, Snapshot, SnapshotMsg};
 
 pub trait ScalarExt {
     fn from_i64(i: i64) -> Scalar;
@@ -15,15 +20,44 @@ impl ScalarExt for Scalar {
         if i >= 0 {
             Scalar::from(i as u64)
         } else {
-            -Scalar::from(-i as u64)
+            -Scalar::from(-(i as i128) as u64)
         }
     }
 }
 
+/// committed variable whose value is only known to prover
+#[derive(Clone)]
+pub struct Committed {
+    pub lc: LinearCombination,
+    pub value: Option<i64>,
+}
+
+/// committed variables of a snapshot
+pub struct SnapshotVars {
+    pub portfolio: LCPortMap,
+    pub sizes: Option<PortMap>,
+    pub pnl: Option<Committed>,
+    pub cum_pnl: Option<Committed>,
+    pub peak_pnl: Option<Committed>,
+    pub hwm: Option<Committed>,
+    pub capital: Option<Committed>,
+    pub cash: Option<Committed>,
+}
+
+/// committed variables of a record, price and symbol are committed only if hidden in message
+pub struct RecordVars {
+    pub qty: Committed,
+    pub price: Option<Committed>,
+    pub symbol: Option<Committed>,
+    pub pnl: LinearCombination,
+}
+
 pub trait ProverExt {
     fn commit_quantity(&mut self, quantity: i64) -> (String, String);
-    fn commit_trade_map(&mut self, trade_map: &TradeMap) -> LCTradeMap;
     fn commit_port_map(&mut self, port_map: &PortMap, port_blnd_map: &PortBlndMap) -> LCPortMap;
+    fn commit_opening(&mut self, opening: &Opening) -> LinearCombination;
+    fn commit_records(&mut self, records: &[Record]) -> Vec<RecordVars>;
+    fn commit_snapshot(&mut self, snapshot: &Snapshot) -> SnapshotVars;
 }
 
 impl<'a> ProverExt for Prover<'a, 'a> {
@@ -35,54 +69,105 @@ impl<'a> ProverExt for Prover<'a, 'a> {
         (commitment, blinding)
     }
 
-    fn commit_trade_map(&mut self, trade_map: &TradeMap) -> LCTradeMap {
-        trade_map.iter().fold(LinkedHashMap::new(), |mut acc, (inst, trades)| {
-            let pairs: Vec<(f64, LinearCombination)> = trades.iter().map(|t| {
-                (t.price, self.commit(Scalar::from_i64(t.qty), get_scalar(&t.qty_blnd)).1.into())
-            }).collect();
-            acc.insert(inst.clone(), pairs);
-            acc
-        })
-    }
-
     fn commit_port_map(&mut self, port_map: &PortMap, port_blnd_map: &PortBlndMap) -> LCPortMap {
         let mut lc_port_map: LCPortMap = LinkedHashMap::new();
         for (symbol, qty) in port_map {
             let variable = self.commit(Scalar::from_i64(*qty), get_scalar(&port_blnd_map[symbol])).1;
+            constrain::signed_range(self, variable.into(), Some(*qty), RANGE_BITS).unwrap();
             lc_port_map.insert(symbol.clone(), variable.into());
         }
         lc_port_map
     }
+
+    fn commit_opening(&mut self, opening: &Opening) -> LinearCombination {
+        self.commit(Scalar::from_i64(opening.value), get_scalar(&opening.blnd)).1.into()
+    }
+
+    fn commit_records(&mut self, records: &[Record]) -> Vec<RecordVars> {
+        records.iter().map(|r|{
+            let t = &r.trade;
+            let qty = self.commit_opening(&Opening { value: t.qty, blnd: t.qty_blnd.clone() });
+            constrain::signed_range(self, qty.clone(), Some(t.qty), RANGE_BITS).unwrap();
+            let price = t.price_blnd.as_ref().map(|blnd|{
+                let price = i64::integerize(t.price);
+                Committed { lc: self.commit_opening(&Opening { value: price, blnd: blnd.clone() }), value: Some(price) }
+            });
+            let symbol = t.symbol_blnd.as_ref().map(|blnd|{
+                let id = digest::symbol_id(&t.symbol);
+                Committed { lc: self.commit_opening(&Opening { value: id, blnd: blnd.clone() }), value: Some(id) }
+            });
+            let pnl = self.commit_opening(&Opening { value: i64::integerize(t.pnl), blnd: t.pnl_blnd.clone() });
+            RecordVars { qty: Committed { lc: qty, value: Some(t.qty) }, price, symbol, pnl }
+        }).collect()
+    }
+
+    fn commit_snapshot(&mut self, snapshot: &Snapshot) -> SnapshotVars {
+        let blnd = &snapshot.snapshot_blnd;
+        let portfolio = self.commit_port_map(&blnd.portfolio, &blnd.portfolio_blnd);
+        let mut commit = |o: &Option<Opening>| o.as_ref().map(|o|{
+            Committed { lc: self.commit_opening(o), value: Some(o.value) }
+        });
+        let pnl = commit(&blnd.pnl);
+        let cum_pnl = commit(&blnd.cum_pnl);
+        let peak_pnl = commit(&blnd.peak_pnl);
+        let hwm = commit(&blnd.hwm);
+        let capital = commit(&blnd.capital);
+        let cash = commit(&blnd.cash);
+        SnapshotVars { portfolio, sizes: Some(blnd.portfolio.clone()), pnl, cum_pnl, peak_pnl, hwm, capital, cash }
+    }
 }
 
 pub trait VerifierExt {
-    fn commit_trade_map(&mut self, message_map: TradeMsgMap) -> LCTradeMap;
     fn commit_port_map(&mut self, port_cmt_map: &PortCmtMap) -> LCPortMap;
+    fn commit_cmt(&mut self, cmt: &str) -> LinearCombination;
+    fn commit_records(&mut self, records: &[BlindedRecord]) -> Vec<RecordVars>;
+    fn commit_snapshot(&mut self, msg: &SnapshotMsg) -> SnapshotVars;
 }
 
 impl<'a> VerifierExt for Verifier<'a> {
-    fn commit_trade_map(&mut self, message_map: TradeMsgMap) -> LCTradeMap {
-        message_map.iter().fold(LinkedHashMap::new(), |mut acc, (symbol, messages)| {
-            let pairs: Vec<(f64, LinearCombination)> = messages.iter().map(|m| {
-                let bytes = base64::decode(&m.qty).unwrap();
-                let commitment = CompressedRistretto::from_slice(&bytes);
-                (m.price, self.commit(commitment).into())
-            }).collect();
-            acc.insert(symbol.clone(), pairs);
-            acc
-        })
-    }
-
     fn commit_port_map(&mut self, port_cmt_map: &PortCmtMap) -> LCPortMap {
         let mut lc_port_map: LCPortMap = LinkedHashMap::new();
         for (symbol, cmt) in port_cmt_map {
             let bytes = base64::decode(cmt).unwrap();
             let commitment = CompressedRistretto::from_slice(&bytes);
             let variable = self.commit(commitment);
+            constrain::signed_range(self, variable.into(), None, RANGE_BITS).unwrap();
             lc_port_map.insert(symbol.clone(), variable.into());
         }
         lc_port_map
     }
+
+    fn commit_cmt(&mut self, cmt: &str) -> LinearCombination {
+        let bytes = base64::decode(cmt).unwrap();
+        let commitment = CompressedRistretto::from_slice(&bytes);
+        self.commit(commitment).into()
+    }
+
+    fn commit_records(&mut self, records: &[BlindedRecord]) -> Vec<RecordVars> {
+        records.iter().map(|r|{
+            let m = &r.msg;
+            let qty = self.commit_cmt(&m.qty);
+            constrain::signed_range(self, qty.clone(), None, RANGE_BITS).unwrap();
+            let price = m.price_cmt.as_ref().map(|c|Committed { lc: self.commit_cmt(c), value: None });
+            let symbol = m.symbol_cmt.as_ref().map(|c|Committed { lc: self.commit_cmt(c), value: None });
+            let pnl = self.commit_cmt(&m.pnl);
+            RecordVars { qty: Committed { lc: qty, value: None }, price, symbol, pnl }
+        }).collect()
+    }
+
+    fn commit_snapshot(&mut self, msg: &SnapshotMsg) -> SnapshotVars {
+        let portfolio = self.commit_port_map(&msg.portfolio);
+        let mut commit = |c: &Option<String>| c.as_ref().map(|c|{
+            Committed { lc: self.commit_cmt(c), value: None }
+        });
+        let pnl = commit(&msg.pnl_cmt);
+        let cum_pnl = commit(&msg.cum_pnl_cmt);
+        let peak_pnl = commit(&msg.peak_pnl_cmt);
+        let hwm = commit(&msg.hwm_cmt);
+        let capital = commit(&msg.capital_cmt);
+        let cash = commit(&msg.cash_cmt);
+        SnapshotVars { portfolio, sizes: None, pnl, cum_pnl, peak_pnl, hwm, capital, cash }
+    }
 }
 
 fn get_scalar(base64_str: &str) -> Scalar {
@@ -95,6 +180,32 @@ fn get_scalar(base64_str: &str) -> Scalar {
     Scalar::from_bits(bytes)
 }
 
+/// sum of commitments, which commits to the sum of values with the sum of blindings
+pub fn add_commitments<'a, I: IntoIterator<Item=&'a String>>(cmts: I) -> String {
+    let sum = cmts.into_iter().fold(RistrettoPoint::identity(), |acc, cmt|{
+        let bytes = base64::decode(cmt).unwrap();
+        acc + CompressedRistretto::from_slice(&bytes).decompress().expect("commitment format invalid")
+    });
+    base64::encode(&sum.compress().to_bytes())
+}
+
+pub fn add_blindings<'a, I: IntoIterator<Item=&'a String>>(blnds: I) -> String {
+    let sum = blnds.into_iter().fold(Scalar::zero(), |acc, blnd|acc + get_scalar(blnd));
+    base64::encode(&sum.to_bytes())
+}
+
+/// whether commitment opens to value with blinding
+pub fn opens_to(cmt: &str, value: i64, blnd: &str) -> bool {
+    let commitment = PedersenGens::default().commit(Scalar::from_i64(value), get_scalar(blnd));
+    base64::encode(&commitment.compress().to_bytes()) == cmt
+}
+
+/// generators capacity should cover all multipliers allocated by range proofs
+pub fn make_bp_gens<CS: ConstraintSystem>(cs: &CS) -> BulletproofGens {
+    let capacity = cs.metrics().multipliers.next_power_of_two();
+    BulletproofGens::new(capacity.max(128), 1)
+}
+
 pub struct R1CSConfig {
     pub pc_gens: PedersenGens,
     transcript: Transcript,
@@ -108,6 +219,11 @@ impl R1CSConfig {
         }
     }
 
+    /// static transcript is required by R1CS so make one from transcript read from proof using Box
+    pub fn from_transcript(transcript: &str) -> R1CSConfig {
+        R1CSConfig::new(Box::leak(transcript.to_string().into_boxed_str()))
+    }
+
     pub fn make_prover(&mut self) -> Prover {
         Prover::new(&self.pc_gens, &mut self.transcript)
     }
@@ -2,6 +2,7 @@
 extern crate lazy_static;
 
 mod api;
+mod circuit;
 mod cmd;
 mod core;
 mod collection;
@@ -14,6 +15,8 @@ mod model;
 mod proof;
 mod report;
 mod sig;
+#[cfg(test)]
+mod testing;
 mod time;
 
 lazy_static! {
@@ -69,13 +72,87 @@ fn main() -> Result<()> {
             cmd::snapshot()?;
         },
         Some(&"prove") => {
-            cmd::prove()?;
+            match args.get(2) {
+                Some(&"aggregate") => cmd::prove_aggregate()?,
+                Some(&"hitrate") => match (args.get(3), args.get(4), args.get(5), args.get(6), args.get(7)) {
+                    (Some(positive), None, _, _, _) => cmd::prove_hit_rate(positive.parse::<u64>()?, None)?,
+                    (Some(positive), Some(&"from"), Some(start), Some(&"to"), Some(end)) => cmd::prove_hit_rate(positive.parse::<u64>()?, Some((start, end)))?,
+                    _ => println!("{}", "please specify positive count following format:\nprove hitrate <count> [from <snapshot> to <snapshot>]"),
+                },
+                Some(&"trade") => match args.get(3) {
+                    Some(record) => cmd::prove_trade(record)?,
+                    None => println!("{}", "please specify record following format:\nprove trade <record>"),
+                },
+                Some(&"solvency") => match args.get(3) {
+                    Some(liabilities) => cmd::prove_solvency(liabilities.parse::<f64>()?)?,
+                    None => println!("{}", "please specify liabilities following format:\nprove solvency <liabilities>"),
+                },
+                Some(&"fund") => {
+                    let traders: Vec<(&str, &str)> = args[3..].chunks(2).filter(|c|c.len() == 2).map(|c|(c[0], c[1])).collect();
+                    if traders.is_empty() || args[3..].len() % 2 != 0 {
+                        println!("{}", "please specify traders following format:\nprove fund <album_file> <pub_key> [<album_file> <pub_key> ...]");
+                    } else {
+                        cmd::prove_fund(&traders)?;
+                    }
+                },
+                Some(&"from") => match (args.get(3), args.get(4), args.get(5)) {
+                    (Some(start), Some(&"to"), Some(end)) => cmd::prove_window(start, end)?,
+                    _ => println!("{}", "please specify snapshots following format:\nprove from <snapshot> to <snapshot>"),
+                },
+                None => cmd::prove()?,
+                _ => println!("{}", constants::HELP_INFO),
+            }
         },
-        Some(&"verify") => {
-            if let Some(proof_file_path) = args.get(2) {
-                cmd::verify(proof_file_path)?;
+        Some(&"open") => {
+            if args.get(2).is_none() || args.get(3).is_none() {
+                println!("{}", "please specify snapshot and symbols following format:\nopen <snapshot> <symbol> [<symbol> ...]");
+            } else {
+                cmd::open(&args[2], &args[3..])?;
+            }
+        },
+        Some(&"audit") => {
+            if args.get(2).is_none() || args.get(3).is_none() {
+                println!("{}", "please specify challenge and sample count following format:\naudit <challenge> <count>");
             } else {
-                cmd::verify_all()?;
+                cmd::audit(&args[2], args[3].parse::<usize>()?)?;
+            }
+        },
+        Some(&"verify") => {
+            match args.get(2) {
+                Some(&"aggregate") => match args.get(3) {
+                    Some(proof_file_path) => cmd::verify_aggregate(proof_file_path)?,
+                    None => println!("{}", "please specify proof file following format:\nverify aggregate <proof_file>"),
+                },
+                Some(&"opening") => match args.get(3) {
+                    Some(opening_file_path) => cmd::verify_opening(opening_file_path)?,
+                    None => println!("{}", "please specify opening file following format:\nverify opening <opening_file>"),
+                },
+                Some(&"hitrate") => match args.get(3) {
+                    Some(proof_file_path) => cmd::verify_hit_rate(proof_file_path)?,
+                    None => println!("{}", "please specify proof file following format:\nverify hitrate <proof_file>"),
+                },
+                Some(&"audit") => match (args.get(3), args.get(4)) {
+                    (Some(response_file_path), Some(count)) => cmd::verify_audit(response_file_path, count.parse::<usize>()?)?,
+                    _ => println!("{}", "please specify response file and sample count following format:\nverify audit <response_file> <count>"),
+                },
+                Some(&"trade") => match args.get(3) {
+                    Some(proof_file_path) => cmd::verify_trade(proof_file_path)?,
+                    None => println!("{}", "please specify proof file following format:\nverify trade <proof_file>"),
+                },
+                Some(&"solvency") => match args.get(3) {
+                    Some(proof_file_path) => cmd::verify_solvency(proof_file_path)?,
+                    None => println!("{}", "please specify proof file following format:\nverify solvency <proof_file>"),
+                },
+                Some(&"fund") => match args.get(3) {
+                    Some(proof_file_path) => cmd::verify_fund(proof_file_path)?,
+                    None => println!("{}", "please specify proof file following format:\nverify fund <proof_file>"),
+                },
+                Some(&"window") => match args.get(3) {
+                    Some(proof_file_path) => cmd::verify_window(proof_file_path)?,
+                    None => println!("{}", "please specify proof file following format:\nverify window <proof_file>"),
+                },
+                Some(proof_file_path) => cmd::verify(proof_file_path)?,
+                None => cmd::verify_all()?,
             }
         },
         Some(&"show") => {
@@ -93,6 +170,7 @@ fn main() -> Result<()> {
                 Some(&"snapshot") => {
                     cmd::show_snapshot()?;
                 },
+                Some(&"key") => cmd::show_key()?,
                 Some(&"report") => {
                     let range = time::TimeRange::new(args.get(3), args.get(4), args.get(5), args.get(6))?;
                     cmd::show_report(range)?;
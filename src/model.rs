@@ -2,13 +2,11 @@ use linked_hash_map::LinkedHashMap;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, FixedOffset};
 use bulletproofs::r1cs::LinearCombination;
-use crate::{collection, core, digest, sig};
+use crate::{collection, core, digest, sig, time};
 use crate::core::{Integerize, Deintegerize};
 use crate::extension::{R1CSConfig, ProverExt};
 use crate::{Result, ZKPNL_CONFIG};
 
-pub type TradeMap = LinkedHashMap<String, Vec<Trade>>;
-pub type TradeMsgMap = LinkedHashMap<String, Vec<TradeMsg>>;
 pub type LCTradeMap = LinkedHashMap<String, Vec<(f64, LinearCombination)>>;
 pub type I64TradeMap = LinkedHashMap<String, Vec<(f64, i64)>>;
 pub type PriceMap = LinkedHashMap<String, f64>;
@@ -16,6 +14,7 @@ pub type PortMap = LinkedHashMap<String, i64>;
 pub type LCPortMap = LinkedHashMap<String, LinearCombination>;
 pub type PortCmtMap = LinkedHashMap<String, String>;
 pub type PortBlndMap = LinkedHashMap<String, String>;
+pub type LimitMap = LinkedHashMap<String, u64>;
 
 #[derive(Deserialize)]
 pub struct ZKPNLConfig {
@@ -29,12 +28,57 @@ pub struct ZKPNLConfig {
     pub ed25519_seed: &'static str,
     pub time_zone: i32,
     pub capital: f64,
+    #[serde(default)]
+    pub position_limit: LimitMap,
+    #[serde(default)]
+    pub pnl_disclosure: Option<PnlClaim>,
+    #[serde(default)]
+    pub leverage: Option<f64>,
+    #[serde(default)]
+    pub max_drawdown: Option<f64>,
+    #[serde(default)]
+    pub hide_price: bool,
+    #[serde(default)]
+    pub hide_symbol: bool,
+    #[serde(default)]
+    pub padding: usize,
+    #[serde(default)]
+    pub time_granularity: Option<time::Granularity>,
+    #[serde(default)]
+    pub performance_fee: Option<f64>,
+    #[serde(default)]
+    pub merkle_records: bool,
+    #[serde(default)]
+    pub benchmark: Option<String>,
+    #[serde(default)]
+    pub restricted: Vec<String>,
+    #[serde(default)]
+    pub turnover: Option<f64>,
+    #[serde(default)]
+    pub hide_capital: bool,
+    #[serde(default)]
+    pub btc_pnl: bool,
 }
 
 impl ZKPNLConfig {
     pub fn is_option(&self, inst: &str) -> bool {
         !self.bitmex.contains(&inst) && !self.binance.contains(&inst)
     }
+
+    /// trade price is committed if configured, or if padded since dummies would stand out by their price
+    pub fn hides_price(&self) -> bool {
+        self.hide_price || self.padding > 0
+    }
+
+    /// trade symbol is committed if configured, or if padded since dummies would stand out by their symbol
+    pub fn hides_symbol(&self) -> bool {
+        self.hide_symbol || self.padding > 0
+    }
+
+    /// market price at trade time is signed and published in proofs unless it would tell hidden trade price or exact time
+    pub fn publishes_record_prices(&self) -> bool {
+        !self.hides_price() && self.time_granularity.is_none()
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -48,10 +92,21 @@ pub struct TradeMsg {
     pub time: DateTime<FixedOffset>,
     pub r#type: TradeType,
     pub prev_hash: String,
-    pub symbol: String,
-    pub price: f64,
+    /// absent if symbol is committed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    /// absent if price is committed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_cmt: Option<String>,
     pub qty: String,
     pub pnl: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol_cmt: Option<String>,
+    /// hash of market price at trade time, which binds the price map published in proofs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_map_hash: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -60,6 +115,12 @@ pub struct Trade {
     pub r#type: TradeType,
     pub symbol: String,
     pub price: f64,
+    /// blinding of price_cmt in trade message
+    #[serde(default)]
+    pub price_blnd: Option<String>,
+    /// blinding of symbol_cmt in trade message
+    #[serde(default)]
+    pub symbol_blnd: Option<String>,
     pub qty: i64,
     pub qty_blnd: String,
     /// cumulative pnl since first trade
@@ -87,15 +148,71 @@ pub struct BlindedRecord {
     pub msg: TradeMsg,
 }
 
+/// value and blinding of a commitment
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Opening {
+    pub value: i64,
+    pub blnd: String,
+}
+
+/// claim of lower <= pnl < upper, an absent bound means unbounded
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PnlClaim {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lower: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upper: Option<f64>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct SnapshotMsg {
     pub time: DateTime<FixedOffset>,
     pub prev_hash: String,
-    pub capital: f64,
-    /// pnl since previous snapshot
-    pub pnl: f64,
-    pub log_return: f64,
+    /// absent if capital is committed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capital: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capital_cmt: Option<String>,
+    /// simple return since previous snapshot, published in place of pnl if capital is committed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#return: Option<f64>,
+    /// pnl since previous snapshot, absent if only a claim is disclosed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pnl: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_return: Option<f64>,
+    /// change of equity in BTC at XBTUSD price since previous snapshot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub btc_pnl: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pnl_cmt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pnl_claim: Option<PnlClaim>,
+    /// cumulative pnl since first snapshot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cum_pnl_cmt: Option<String>,
+    /// running peak of cumulative pnl
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_pnl_cmt: Option<String>,
+    /// running high-water mark of equity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hwm_cmt: Option<String>,
+    /// performance fee charged on equity above previous high-water mark
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee: Option<f64>,
+    /// capital plus cumulative pnl less market value of portfolio
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cash_cmt: Option<String>,
     pub portfolio: PortCmtMap,
+    /// hash of market price at snapshot time, which binds market price attached by proofs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_map_hash: Option<String>,
+    /// hash of the latest record at snapshot time, which anchors records after this snapshot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_record: Option<String>,
+    /// merkle root of record hashes, records are left out of message if present
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub records_root: Option<String>,
     /// records since previous snapshot
     pub records: Vec<BlindedRecord>,
 }
@@ -107,6 +224,19 @@ pub struct SnapshotBlnd {
     pub portfolio_blnd: PortBlndMap,
     pub records: Vec<Record>,
     pub market_price: PriceMap,
+    /// opening of pnl_cmt in snapshot message
+    #[serde(default)]
+    pub pnl: Option<Opening>,
+    #[serde(default)]
+    pub cum_pnl: Option<Opening>,
+    #[serde(default)]
+    pub peak_pnl: Option<Opening>,
+    #[serde(default)]
+    pub hwm: Option<Opening>,
+    #[serde(default)]
+    pub capital: Option<Opening>,
+    #[serde(default)]
+    pub cash: Option<Opening>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -123,6 +253,9 @@ pub struct BlindedSnapshot {
     pub sig: String,
     pub msg: SnapshotMsg,
     pub market_price: PriceMap,
+    /// records committed by merkle root in message, only attached by proofs which prove them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub records: Vec<BlindedRecord>,
 }
 
 impl TradeType {
@@ -157,16 +290,43 @@ impl Record {
         let mut prover = config.make_prover();
         let (qty_cmt, qty_blnd) = prover.commit_quantity(qty);
         let (pnl_cmt, pnl_blnd) = prover.commit_quantity(i64::integerize(pnl));
+        let symbol_opening = if ZKPNL_CONFIG.hides_symbol() {
+            Some(prover.commit_quantity(digest::symbol_id(symbol)))
+        } else {
+            None
+        };
+        // inherit and deliver take market price so only trade price is worth hiding,
+        // unless market price of record is left out of proof, which their price would tell
+        let price_opening = match (ZKPNL_CONFIG.hides_price(), r#type) {
+            (true, TradeType::Trade) => Some(prover.commit_quantity(i64::integerize(price))),
+            (_, TradeType::Inherit) | (_, TradeType::Deliver) if !ZKPNL_CONFIG.publishes_record_prices() => {
+                Some(prover.commit_quantity(i64::integerize(price)))
+            },
+            _ => None,
+        };
+        // market price of inherit and deliver is selected in proof so it would tell the hidden symbol
+        let publishes_price = match (&price_opening, &symbol_opening, r#type) {
+            (Some(_), _, _) => false,
+            (None, Some(_), TradeType::Inherit) | (None, Some(_), TradeType::Deliver) => false,
+            _ => true,
+        };
 
         let msg = TradeMsg {
-            time, r#type, price, prev_hash,
-            symbol: symbol.to_string(),
+            time: ZKPNL_CONFIG.time_granularity.map_or(time, |g|time::coarsen(time, g)),
+            r#type, prev_hash,
+            symbol: if symbol_opening.is_some() { None } else { Some(symbol.to_string()) },
+            price: if publishes_price { Some(price) } else { None },
+            price_cmt: price_opening.as_ref().map(|(cmt, _)|cmt.clone()),
             qty: qty_cmt,
             pnl: pnl_cmt,
+            symbol_cmt: symbol_opening.as_ref().map(|(cmt, _)|cmt.clone()),
+            price_map_hash: if ZKPNL_CONFIG.publishes_record_prices() { Some(digest::price_map_hash(price_map)) } else { None },
         };
         let trade = Trade {
             time, r#type,
             symbol: symbol.to_string(),
+            price_blnd: price_opening.map(|(_, blnd)|blnd),
+            symbol_blnd: symbol_opening.map(|(_, blnd)|blnd),
             price, qty, qty_blnd, pnl, pnl_blnd
         };
         let hash = digest::sha256(String::from(&msg).as_ref());
@@ -174,14 +334,24 @@ impl Record {
         let sig = sig::sign(&hash)?;
         Ok(Record { hash, sig, msg, trade })
     }
+
+    /// link record to another previous record, which changes its hash and signature
+    pub fn relink(&mut self, prev_hash: &str) -> Result<()> {
+        self.msg.prev_hash = prev_hash.to_string();
+        self.hash = digest::sha256(String::from(&self.msg).as_ref());
+        println!("{}", "sign hash");
+        self.sig = sig::sign(&self.hash)?;
+        Ok(())
+    }
 }
 
 impl Snapshot {
     pub fn new(time: DateTime<FixedOffset>, album: &[Snapshot], mut records: Vec<Record>, price_map: &PriceMap) -> Result<Snapshot> {
         let genesis_hash = digest::sha256(&ZKPNL_CONFIG.transcript);
         let prev_hash = album.last().map_or(&genesis_hash, |s|&s.hash).to_string();
+        let last_record = records.last().map(|r|r.hash.clone());
         records.retain(|r|{
-            album.last().map_or(true, |s|r.msg.time > s.msg.time)
+            album.last().map_or(true, |s|r.trade.time > s.msg.time)
         });
         let mut trade_map = collection::get_i64_trade_map(&records);
         if !album.is_empty() {
@@ -189,11 +359,48 @@ impl Snapshot {
             let prev_price = &album.last().unwrap().snapshot_blnd.market_price;
             core::inherit_portfolio(&prev_port, &mut trade_map, &prev_price);
         }
-        let curt_port = core::calc_portfolio(&trade_map);
-        let pnl = core::calc_total_pnl(&trade_map, &price_map).deintegerize();
+        let mut curt_port = core::calc_portfolio(&trade_map);
+        // committing every instrument of market price hides which ones are held
+        if ZKPNL_CONFIG.hides_symbol() {
+            for symbol in price_map.keys() {
+                curt_port.entry(symbol.clone()).or_insert(0);
+            }
+        }
+        let pnl: i64 = core::calc_total_pnl(&trade_map, &price_map);
+        let log_return = f64::ln((pnl.deintegerize() + ZKPNL_CONFIG.capital) / ZKPNL_CONFIG.capital);
 
         let mut config = R1CSConfig::default();
         let mut prover = config.make_prover();
+        if let Some(claim) = ZKPNL_CONFIG.pnl_disclosure {
+            if !claim.contains(pnl) {
+                return Err("P&L is out of disclosure range, please adjust pnl_disclosure in config".into())
+            }
+        }
+        // pnl along with return would tell committed capital
+        let pnl_opening = if ZKPNL_CONFIG.pnl_disclosure.is_some() || ZKPNL_CONFIG.hide_capital {
+            let (pnl_cmt, pnl_blnd) = prover.commit_quantity(pnl);
+            Some((pnl_cmt, Opening { value: pnl, blnd: pnl_blnd }))
+        } else {
+            None
+        };
+        let capital = i64::integerize(ZKPNL_CONFIG.capital);
+        // snapshot proofs link capital so return can't be measured against a different capital each time
+        if album.last().map_or(false, |s|i64::integerize(s.capital()) != capital) {
+            return Err("capital changed since previous snapshot, please start a new album".into())
+        }
+        // benchmark window of initial snapshot starts from market price signed in its first record
+        if ZKPNL_CONFIG.benchmark.is_some() && album.is_empty() && (!ZKPNL_CONFIG.publishes_record_prices() || records.is_empty()) {
+            return Err("benchmark needs market price published in records of initial snapshot, please unset hide_price, padding and time_granularity and commit a record first".into())
+        }
+        let capital_opening = if ZKPNL_CONFIG.hide_capital {
+            if ZKPNL_CONFIG.leverage.is_some() || ZKPNL_CONFIG.benchmark.is_some() || ZKPNL_CONFIG.performance_fee.is_some() {
+                return Err("leverage, benchmark and performance fee claims need public capital, please unset hide_capital".into())
+            }
+            let (capital_cmt, capital_blnd) = prover.commit_quantity(capital);
+            Some((capital_cmt, Opening { value: capital, blnd: capital_blnd }))
+        } else {
+            None
+        };
         let mut port_cmt: PortBlndMap = LinkedHashMap::new();
         let mut port_blnd: PortBlndMap = LinkedHashMap::new();
         for (symbol, size) in &curt_port {
@@ -201,24 +408,189 @@ impl Snapshot {
             port_cmt.insert(symbol.clone(), size_cmt);
             port_blnd.insert(symbol.clone(), size_blnd);
         }
+        // cumulative pnl of a chain taken by earlier versions can't be linked to its snapshots
+        let prev_cum = match album.last() {
+            None => 0,
+            Some(s) => match &s.snapshot_blnd.cum_pnl {
+                Some(cum) => cum.value,
+                None => return Err("album taken by earlier versions has no cumulative P&L, please start a new album".into()),
+            },
+        };
+        let cum = prev_cum + pnl;
+        let (cum_cmt, cum_blnd) = prover.commit_quantity(cum);
+        let peak_opening = match ZKPNL_CONFIG.max_drawdown {
+            Some(max_drawdown) => {
+                let prev_peak = match album.last() {
+                    None => 0,
+                    Some(s) => match &s.snapshot_blnd.peak_pnl {
+                        Some(peak) => peak.value,
+                        None => return Err("max_drawdown should be configured since the first snapshot".into()),
+                    },
+                };
+                let peak = prev_peak.max(cum);
+                if peak - cum > i64::integerize(max_drawdown) {
+                    println!("{}", "warning: drawdown exceeds max_drawdown, snapshot proof will not verify");
+                }
+                let (peak_cmt, peak_blnd) = prover.commit_quantity(peak);
+                Some((peak_cmt, Opening { value: peak, blnd: peak_blnd }))
+            },
+            None => None,
+        };
+        let hwm_opening = match ZKPNL_CONFIG.performance_fee {
+            Some(rate) => {
+                let equity = i64::integerize(ZKPNL_CONFIG.capital) + cum;
+                let prev_hwm = match album.last() {
+                    None => i64::integerize(ZKPNL_CONFIG.capital),
+                    Some(s) => match &s.snapshot_blnd.hwm {
+                        Some(hwm) => hwm.value,
+                        None => return Err("performance_fee should be configured since the first snapshot".into()),
+                    },
+                };
+                let fee = core::calc_fee(rate, equity - prev_hwm);
+                let hwm = prev_hwm.max(equity);
+                let (hwm_cmt, hwm_blnd) = prover.commit_quantity(hwm);
+                Some((hwm_cmt, Opening { value: hwm, blnd: hwm_blnd }, fee))
+            },
+            None => None,
+        };
+        let btc_pnl = if ZKPNL_CONFIG.btc_pnl {
+            if ZKPNL_CONFIG.hide_capital || ZKPNL_CONFIG.pnl_disclosure.is_some() {
+                return Err("BTC P&L would reveal hidden P&L and capital, please unset pnl_disclosure and hide_capital".into())
+            }
+            let end_price = price_map.get("XBTUSD").ok_or("XBTUSD price is required for BTC P&L")?;
+            // initial snapshot converts starting capital at its own XBTUSD price
+            let (start_equity, start_price) = match album.last() {
+                None => (capital, *end_price),
+                Some(s) => match (&s.snapshot_blnd.cum_pnl, s.snapshot_blnd.market_price.get("XBTUSD")) {
+                    (Some(cum), Some(price)) => (i64::integerize(s.capital()) + cum.value, *price),
+                    _ => return Err("btc_pnl needs cumulative P&L and XBTUSD price in previous snapshot".into()),
+                },
+            };
+            Some(core::calc_btc_pnl(start_equity, core::calc_cents(start_price), capital + cum, core::calc_cents(*end_price)))
+        } else {
+            None
+        };
+        let market_value: i64 = curt_port.iter().map(|(symbol, size)|size * i64::integerize(price_map[symbol])).sum();
+        let cash = capital + cum - market_value;
+        let (cash_cmt, cash_blnd) = prover.commit_quantity(cash);
+        let is_hidden = pnl_opening.is_some();
+        let hashes: Vec<String> = records.iter().map(|r|r.hash.clone()).collect();
         let snapshot = SnapshotMsg {
-            time, prev_hash, pnl,
-            capital: ZKPNL_CONFIG.capital,
-            log_return: f64::ln((pnl + ZKPNL_CONFIG.capital) / ZKPNL_CONFIG.capital),
+            time, prev_hash,
+            capital: if capital_opening.is_some() { None } else { Some(ZKPNL_CONFIG.capital) },
+            capital_cmt: capital_opening.as_ref().map(|(cmt, _)|cmt.clone()),
+            r#return: capital_opening.as_ref().map(|_|core::calc_return(pnl, capital).deintegerize()),
+            pnl: if is_hidden { None } else { Some(pnl.deintegerize()) },
+            log_return: if is_hidden { None } else { Some(log_return) },
+            btc_pnl: btc_pnl.map(|b|b.deintegerize()),
+            pnl_cmt: pnl_opening.as_ref().map(|(cmt, _)|cmt.clone()),
+            pnl_claim: if is_hidden { ZKPNL_CONFIG.pnl_disclosure } else { None },
+            cum_pnl_cmt: Some(cum_cmt),
+            peak_pnl_cmt: peak_opening.as_ref().map(|(cmt, _)|cmt.clone()),
+            hwm_cmt: hwm_opening.as_ref().map(|(cmt, _, _)|cmt.clone()),
+            fee: hwm_opening.as_ref().map(|(_, _, fee)|fee.deintegerize()),
+            cash_cmt: Some(cash_cmt),
             portfolio: port_cmt,
-            records: records.iter().map(BlindedRecord::from).collect(),
+            price_map_hash: Some(digest::price_map_hash(price_map)),
+            last_record,
+            records_root: if ZKPNL_CONFIG.merkle_records { Some(digest::merkle_root(&hashes)) } else { None },
+            records: if ZKPNL_CONFIG.merkle_records { vec![] } else { records.iter().map(BlindedRecord::from).collect() },
         };
         let snapshot_blnd = SnapshotBlnd {
             time, records,
             portfolio: curt_port,
             portfolio_blnd: port_blnd,
             market_price: price_map.clone(),
+            pnl: pnl_opening.map(|(_, opening)|opening),
+            cum_pnl: Some(Opening { value: cum, blnd: cum_blnd }),
+            peak_pnl: peak_opening.map(|(_, opening)|opening),
+            hwm: hwm_opening.map(|(_, opening, _)|opening),
+            capital: capital_opening.map(|(_, opening)|opening),
+            cash: Some(Opening { value: cash, blnd: cash_blnd }),
         };
         let hash = digest::sha256(String::from(&snapshot).as_ref());
         println!("{}", "sign hash");
         let sig = sig::sign(&hash)?;
         Ok(Snapshot { hash, sig, msg: snapshot, snapshot_blnd })
     }
+
+    /// pnl since previous snapshot, read from opening if hidden in message
+    pub fn pnl(&self) -> f64 {
+        self.msg.pnl.unwrap_or_else(||{
+            self.snapshot_blnd.pnl.as_ref().map_or(0.0, |o|o.value.deintegerize())
+        })
+    }
+
+    /// position of record in the merkle tree of this snapshot with its path to root
+    pub fn merkle_path(&self, record_hash: &str) -> Option<Vec<digest::MerkleNode>> {
+        let hashes: Vec<String> = self.snapshot_blnd.records.iter().map(|r|r.hash.clone()).collect();
+        hashes.iter().position(|h|h == record_hash).map(|index|digest::merkle_path(&hashes, index))
+    }
+
+    pub fn log_return(&self) -> f64 {
+        self.msg.log_return.unwrap_or_else(||{
+            f64::ln((self.pnl() + self.capital()) / self.capital())
+        })
+    }
+
+    /// capital read from opening if committed in message
+    pub fn capital(&self) -> f64 {
+        self.msg.capital.unwrap_or_else(||{
+            self.snapshot_blnd.capital.as_ref().map_or(0.0, |o|o.value.deintegerize())
+        })
+    }
+}
+
+impl BlindedSnapshot {
+    /// blinded snapshot with records committed by merkle root attached
+    pub fn with_records(unblinded: &Snapshot) -> BlindedSnapshot {
+        let mut blinded = BlindedSnapshot::from(unblinded);
+        if blinded.msg.records_root.is_some() {
+            blinded.records = unblinded.snapshot_blnd.records.iter().map(BlindedRecord::from).collect();
+        }
+        blinded
+    }
+
+    /// records since previous snapshot, either in message or alongside it
+    pub fn records(&self) -> &[BlindedRecord] {
+        match self.msg.records_root {
+            Some(_) => &self.records,
+            None => &self.msg.records,
+        }
+    }
+
+    /// records alongside message should match its merkle root
+    pub fn verify_records_root(&self) {
+        if let Some(root) = &self.msg.records_root {
+            let hashes: Vec<String> = self.records.iter().map(|r|r.hash.clone()).collect();
+            if &digest::merkle_root(&hashes) != root {
+                panic!("verify records root failed at {}", self.hash)
+            }
+        }
+    }
+
+    /// market price alongside message should match its signed hash
+    pub fn verify_market_price(&self) {
+        if let Some(hash) = &self.msg.price_map_hash {
+            if &digest::price_map_hash(&self.market_price) != hash {
+                panic!("verify market price failed at {}", self.hash)
+            }
+        }
+    }
+}
+
+impl PnlClaim {
+    pub fn lower(&self) -> Option<i64> {
+        self.lower.map(i64::integerize)
+    }
+
+    pub fn upper(&self) -> Option<i64> {
+        self.upper.map(i64::integerize)
+    }
+
+    pub fn contains(&self, pnl: i64) -> bool {
+        self.lower().map_or(true, |l|pnl >= l) && self.upper().map_or(true, |u|pnl < u)
+    }
 }
 
 pub trait Verifiable {
@@ -226,6 +598,7 @@ pub trait Verifiable {
     fn sig(&self) -> &str;
     fn msg(&self) -> String;
     fn prev_hash(&self) -> &str;
+    fn time(&self) -> DateTime<FixedOffset>;
 }
 
 impl Verifiable for Record {
@@ -241,6 +614,9 @@ impl Verifiable for Record {
     fn prev_hash(&self) -> &str {
         &self.msg.prev_hash
     }
+    fn time(&self) -> DateTime<FixedOffset> {
+        self.msg.time
+    }
 }
 
 impl Verifiable for BlindedRecord {
@@ -256,6 +632,9 @@ impl Verifiable for BlindedRecord {
     fn prev_hash(&self) -> &str {
         &self.msg.prev_hash
     }
+    fn time(&self) -> DateTime<FixedOffset> {
+        self.msg.time
+    }
 }
 
 impl Verifiable for Snapshot {
@@ -271,6 +650,9 @@ impl Verifiable for Snapshot {
     fn prev_hash(&self) -> &str {
         &self.msg.prev_hash
     }
+    fn time(&self) -> DateTime<FixedOffset> {
+        self.msg.time
+    }
 }
 
 impl Verifiable for BlindedSnapshot {
@@ -286,6 +668,9 @@ impl Verifiable for BlindedSnapshot {
     fn prev_hash(&self) -> &str {
         &self.msg.prev_hash
     }
+    fn time(&self) -> DateTime<FixedOffset> {
+        self.msg.time
+    }
 }
 
 impl From<&Record> for BlindedRecord {
@@ -298,7 +683,7 @@ impl From<&Record> for BlindedRecord {
 impl From<&Snapshot> for BlindedSnapshot {
     fn from(unblinded: &Snapshot) -> BlindedSnapshot {
         let s = unblinded.clone();
-        BlindedSnapshot { hash: s.hash, sig: s.sig, msg: s.msg, market_price: s.snapshot_blnd.market_price }
+        BlindedSnapshot { hash: s.hash, sig: s.sig, msg: s.msg, market_price: s.snapshot_blnd.market_price, records: vec![] }
     }
 }
 
@@ -324,4 +709,44 @@ impl From<&SnapshotBlnd> for String {
     fn from(s: &SnapshotBlnd) -> String {
         serde_json::to_string(s).unwrap()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pnl_claim_includes_lower_and_excludes_upper() {
+        let claim = PnlClaim { lower: Some(0.0), upper: Some(50_000.0) };
+        assert!(claim.contains(0));
+        assert!(claim.contains(i64::integerize(49_999.999)));
+        assert!(!claim.contains(i64::integerize(50_000.0)));
+        assert!(!claim.contains(-1));
+    }
+
+    #[test]
+    fn pnl_claim_leaves_absent_bound_open() {
+        let claim = PnlClaim { lower: Some(0.0), upper: None };
+        assert!(claim.contains(i64::MAX));
+        assert!(!claim.contains(-1));
+        assert!(PnlClaim { lower: None, upper: None }.contains(i64::MIN));
+    }
+
+    fn config(extra: &'static str) -> ZKPNLConfig {
+        let base = "transcript = \"t\"\nrecord_path = \"\"\nprice_path = \"\"\nalbum_path = \"\"\nproof_path = \"\"\n\
+                    bitmex = []\nbinance = []\ned25519_seed = \"\"\ntime_zone = 0\ncapital = 1.0\n";
+        toml::from_str(Box::leak(format!("{}{}", base, extra).into_boxed_str())).unwrap()
+    }
+
+    #[test]
+    fn padding_hides_price_and_symbol() {
+        let padded = config("padding = 4");
+        assert!(padded.hides_price() && padded.hides_symbol());
+        assert!(!padded.publishes_record_prices());
+        let plain = config("");
+        assert!(!plain.hides_price() && !plain.hides_symbol());
+        assert!(plain.publishes_record_prices());
+        assert!(config("hide_price = true").hides_price());
+        assert!(config("hide_symbol = true").hides_symbol());
+    }
 }
\ No newline at end of file
@@ -1,11 +1,13 @@
+use linked_hash_map::LinkedHashMap;
 use serde::{Deserialize, Serialize};
-use bulletproofs::{BulletproofGens, PedersenGens};
-use bulletproofs::r1cs::{LinearCombination, R1CSProof};
+use bulletproofs::PedersenGens;
+use bulletproofs::r1cs::{LinearCombination, R1CSProof, Verifier};
 use curve25519_dalek::scalar::Scalar;
 use crate::*;
 use crate::model::*;
-use crate::core::Integerize;
-use crate::extension::{R1CSConfig, ProverExt, VerifierExt};
+use crate::core::{Deintegerize, Integerize};
+use crate::circuit::Claims;
+use crate::extension::{R1CSConfig, ProverExt, VerifierExt, SnapshotVars, make_bp_gens};
 
 #[derive(Serialize, Deserialize)]
 pub struct ZKPNLProof {
@@ -13,88 +15,72 @@ pub struct ZKPNLProof {
     pub transcript: String,
     pub ed25519_pub_key: String,
     pub r1cs_proof: String,
+    pub claims: Claims,
+    /// market price at the time of each record in current snapshot, absent if its hash is not signed in record
+    pub record_prices: Vec<Option<PriceMap>>,
     pub current_snapshot: BlindedSnapshot,
     pub previous_snapshot: Option<BlindedSnapshot>,
 }
 
 impl ZKPNLProof {
-    pub fn new(previous: Option<&Snapshot>, current: &Snapshot) -> ZKPNLProof {
+    pub fn new(previous: Option<&Snapshot>, current: &Snapshot, market_prices: &[MarketPrice]) -> ZKPNLProof {
+        ZKPNLProof::with_claims(previous, current, market_prices, Claims::default())
+    }
+
+    pub fn with_claims(previous: Option<&Snapshot>, current: &Snapshot, market_prices: &[MarketPrice], claims: Claims) -> ZKPNLProof {
         let mut r1cs_config = R1CSConfig::default();
         let mut prover = r1cs_config.make_prover();
-        let bp_gens = BulletproofGens::new(128, 1);
 
-        let trade_map = collection::get_trade_map(&current.snapshot_blnd.records);
-        let mut lc_trade_map = prover.commit_trade_map(&trade_map);
-        if let Some(previous) = previous {
-            let prev_lc_port_map = prover.commit_port_map(&previous.snapshot_blnd.portfolio, &previous.snapshot_blnd.portfolio_blnd);
-            core::inherit_portfolio(&prev_lc_port_map, &mut lc_trade_map, &previous.snapshot_blnd.market_price);
-        }
-        let lc_pnl = core::calc_total_pnl::<LinearCombination, Scalar>(&lc_trade_map, &current.snapshot_blnd.market_price);
-        let expected = Scalar::integerize(current.msg.pnl);
-        constrain::equal(&mut prover, lc_pnl, expected);
-
-        let curt_lc_port_map = prover.commit_port_map(&current.snapshot_blnd.portfolio, &current.snapshot_blnd.portfolio_blnd);
-        let expected_lc_port_map = core::calc_portfolio::<LinearCombination, Scalar>(&lc_trade_map);
-        for (symbol, lc_size) in curt_lc_port_map {
-            constrain::equal(&mut prover, lc_size, expected_lc_port_map[&symbol].clone());
-        }
+        let record_prices = get_record_prices(&current.snapshot_blnd.records, market_prices);
+        let record_vars = prover.commit_records(&current.snapshot_blnd.records);
+        let prev_vars = previous.map(|p|prover.commit_snapshot(p));
+        let curt_vars = prover.commit_snapshot(current);
+        let previous_snapshot = previous.map(BlindedSnapshot::from);
+        let current_snapshot = BlindedSnapshot::with_records(current);
+        let prev = previous_snapshot.as_ref().map(|p|(p, prev_vars.as_ref().unwrap()));
+        circuit::constrain_link(&mut prover, &claims, prev, (&current_snapshot, &curt_vars), &record_vars, &record_prices).unwrap();
 
+        let bp_gens = make_bp_gens(&prover);
         ZKPNLProof {
             protocol_version: constants::PROTOCOL_VERSION,
             transcript: ZKPNL_CONFIG.transcript.to_string(),
             ed25519_pub_key: sig::get_pub_key_str(),
             r1cs_proof: base64::encode(&prover.prove(&bp_gens).unwrap().to_bytes()),
-            current_snapshot: BlindedSnapshot::from(current),
-            previous_snapshot: previous.map(BlindedSnapshot::from),
+            claims,
+            record_prices,
+            current_snapshot,
+            previous_snapshot,
         }
     }
 
     pub fn verify_r1cs(&self) -> Result<()> {
-        // static transcript is required by R1CS so make one here using Box
-        let transcript: &'static str = Box::leak(self.transcript.clone().into_boxed_str());
-        let mut r1cs_config = R1CSConfig::new(transcript);
+        let mut r1cs_config = R1CSConfig::from_transcript(&self.transcript);
         let mut verifier = r1cs_config.make_verifier();
 
-        let trade_map = collection::get_trade_msg_map(&self.current_snapshot.msg.records);
-        let mut lc_trade_map = verifier.commit_trade_map(trade_map);
-        if let Some(previous) = &self.previous_snapshot {
-            let lc_port_map = verifier.commit_port_map(&previous.msg.portfolio);
-            core::inherit_portfolio(&lc_port_map, &mut lc_trade_map, &previous.market_price);
-        }
-        let lc_pnl = core::calc_total_pnl::<LinearCombination, Scalar>(&lc_trade_map, &self.current_snapshot.market_price);
-        let expected = Scalar::integerize(self.current_snapshot.msg.pnl);
-        constrain::equal(&mut verifier, lc_pnl, expected);
-
-        let curt_lc_port_map = verifier.commit_port_map(&self.current_snapshot.msg.portfolio);
-        let expected_lc_port_map = core::calc_portfolio::<LinearCombination, Scalar>(&lc_trade_map);
-        for (symbol, lc_size) in curt_lc_port_map {
-            constrain::equal(&mut verifier, lc_size, expected_lc_port_map[&symbol].clone());
-        }
+        let record_vars = verifier.commit_records(self.current_snapshot.records());
+        let prev_vars = self.previous_snapshot.as_ref().map(|p|verifier.commit_snapshot(&p.msg));
+        let curt_vars = verifier.commit_snapshot(&self.current_snapshot.msg);
+        let prev = self.previous_snapshot.as_ref().map(|p|(p, prev_vars.as_ref().unwrap()));
+        circuit::constrain_link(&mut verifier, &self.claims, prev, (&self.current_snapshot, &curt_vars), &record_vars, &self.record_prices)?;
 
         println!("{}", "verify r1cs proof");
-        let proof_bytes = base64::decode(&self.r1cs_proof)?;
-        let proof = R1CSProof::from_bytes(&proof_bytes).unwrap();
-        let pc_gens = PedersenGens::default();
-        let bp_gens = BulletproofGens::new(128, 1);
-        let result = verifier.verify(&proof, &pc_gens, &bp_gens);
-        match result {
-            Ok(()) => println!("{}", "verify OK"),
-            Err(e) => panic!("{}", e),
-        };
-        Ok(())
+        verify_r1cs_proof(verifier, &self.r1cs_proof)
     }
 
     pub fn verify_hash(&self) -> Result<()> {
-        digest::verify_msg_hashes(&self.current_snapshot.msg.records);
+        digest::verify_msg_hashes(self.current_snapshot.records());
         println!("{}", "verify snapshot hash");
         digest::verify_msg_hash(&self.current_snapshot);
+        self.current_snapshot.verify_records_root();
+        self.current_snapshot.verify_market_price();
+        self.previous_snapshot.iter().for_each(BlindedSnapshot::verify_market_price);
         if self.previous_snapshot.is_none() {
-            digest::verify_hash_chain_since_genesis(&self.transcript, &self.current_snapshot.msg.records);
+            digest::verify_hash_chain_since_genesis(&self.transcript, self.current_snapshot.records());
             if self.current_snapshot.prev_hash() != digest::sha256(&self.transcript) {
                 panic!("verify initial snapshot hash chain failed")
             }
         } else {
-            digest::verify_hash_chain(&self.current_snapshot.msg.records);
+            digest::verify_hash_chain(self.current_snapshot.records());
             if self.current_snapshot.prev_hash() != digest::sha256(&String::from(&self.previous_snapshot.as_ref().unwrap().msg)) {
                 panic!("verify snapshot hash chain failed")
             }
@@ -102,15 +88,1042 @@ impl ZKPNLProof {
         Ok(())
     }
 
+    /// proofs should link to each other as a snapshot chain from the initial snapshot and claim the same max drawdown and fee rate
+    pub fn verify_chain(proofs: &mut Vec<ZKPNLProof>) -> Result<()> {
+        println!("{}", "verify snapshot chain");
+        proofs.sort_by_key(|p|p.current_snapshot.msg.time);
+        // a chain cut after genesis would drop earlier losses from cumulative claims
+        if proofs.first().map_or(false, |p|p.previous_snapshot.is_some()) {
+            panic!("snapshot chain does not start from initial snapshot")
+        }
+        for (previous, current) in proofs.iter().zip(proofs.iter().skip(1)) {
+            if current.current_snapshot.prev_hash() != previous.current_snapshot.hash {
+                panic!("snapshot chain broken at {}", current.current_snapshot.hash)
+            }
+            if current.claims.max_drawdown != previous.claims.max_drawdown {
+                panic!("max drawdown claim changed at {}", current.current_snapshot.hash)
+            }
+            if current.claims.performance_fee != previous.claims.performance_fee {
+                panic!("performance fee rate changed at {}", current.current_snapshot.hash)
+            }
+        }
+        Ok(())
+    }
+
     pub fn verify_sig(&self) -> Result<()> {
-        if self.ed25519_pub_key.is_empty() {
-            println!("{}", "warning: no pub key found in proof, skip signature verification.");
-        } else {
-            let pk = sig::get_pub_key_from_str(&self.ed25519_pub_key)?;
-            sig::verify_sigs_with_pk(&pk, &self.current_snapshot.msg.records)?;
+        if let Some(pk) = sig::get_proof_pub_key(&self.ed25519_pub_key)? {
+            sig::verify_sigs_with_pk(&pk, self.current_snapshot.records())?;
             println!("{}", "verify snapshot signature");
             sig::verify_sig_with_pk(&pk, &self.current_snapshot)?;
         }
         Ok(())
     }
+}
+
+/// one proof over the whole snapshot chain since genesis, committing each portfolio once
+#[derive(Serialize, Deserialize)]
+pub struct AggregateProof {
+    pub protocol_version: u32,
+    pub transcript: String,
+    pub ed25519_pub_key: String,
+    pub r1cs_proof: String,
+    pub claims: Claims,
+    /// market price at the time of each record, per snapshot
+    pub record_prices: Vec<Vec<Option<PriceMap>>>,
+    pub snapshots: Vec<BlindedSnapshot>,
+}
+
+impl AggregateProof {
+    pub fn new(album: &[Snapshot], market_prices: &[MarketPrice]) -> AggregateProof {
+        let mut r1cs_config = R1CSConfig::default();
+        let mut prover = r1cs_config.make_prover();
+        let claims = Claims::default();
+
+        let snapshots: Vec<BlindedSnapshot> = album.iter().map(BlindedSnapshot::with_records).collect();
+        let mut record_prices = vec![];
+        let mut previous: Option<(&BlindedSnapshot, SnapshotVars)> = None;
+        for (snapshot, blinded) in album.iter().zip(&snapshots) {
+            let prices = get_record_prices(&snapshot.snapshot_blnd.records, market_prices);
+            let record_vars = prover.commit_records(&snapshot.snapshot_blnd.records);
+            let curt_vars = prover.commit_snapshot(snapshot);
+            let prev = previous.as_ref().map(|(p, v)|(*p, v));
+            circuit::constrain_link(&mut prover, &claims, prev, (blinded, &curt_vars), &record_vars, &prices).unwrap();
+            record_prices.push(prices);
+            previous = Some((blinded, curt_vars));
+        }
+
+        let bp_gens = make_bp_gens(&prover);
+        let r1cs_proof = base64::encode(&prover.prove(&bp_gens).unwrap().to_bytes());
+        AggregateProof {
+            protocol_version: constants::PROTOCOL_VERSION,
+            transcript: ZKPNL_CONFIG.transcript.to_string(),
+            ed25519_pub_key: sig::get_pub_key_str(),
+            r1cs_proof,
+            claims,
+            record_prices,
+            snapshots,
+        }
+    }
+
+    pub fn verify_r1cs(&self) -> Result<()> {
+        let mut r1cs_config = R1CSConfig::from_transcript(&self.transcript);
+        let mut verifier = r1cs_config.make_verifier();
+
+        if self.record_prices.len() != self.snapshots.len() {
+            panic!("market price of snapshots incomplete")
+        }
+        let mut previous: Option<(&BlindedSnapshot, SnapshotVars)> = None;
+        for (snapshot, prices) in self.snapshots.iter().zip(&self.record_prices) {
+            let record_vars = verifier.commit_records(snapshot.records());
+            let curt_vars = verifier.commit_snapshot(&snapshot.msg);
+            let prev = previous.as_ref().map(|(p, v)|(*p, v));
+            circuit::constrain_link(&mut verifier, &self.claims, prev, (snapshot, &curt_vars), &record_vars, prices)?;
+            previous = Some((snapshot, curt_vars));
+        }
+
+        println!("verify r1cs proof of {} snapshots", self.snapshots.len());
+        verify_r1cs_proof(verifier, &self.r1cs_proof)
+    }
+
+    pub fn verify_hash(&self) -> Result<()> {
+        let records: Vec<BlindedRecord> = self.snapshots.iter().flat_map(|s|s.records().to_vec()).collect();
+        digest::verify_msg_hashes(&records);
+        digest::verify_hash_chain_since_genesis(&self.transcript, &records);
+        println!("{}", "verify snapshot hash");
+        digest::verify_msg_hashes(&self.snapshots);
+        digest::verify_hash_chain_since_genesis(&self.transcript, &self.snapshots);
+        self.snapshots.iter().for_each(BlindedSnapshot::verify_records_root);
+        self.snapshots.iter().for_each(BlindedSnapshot::verify_market_price);
+        Ok(())
+    }
+
+    pub fn verify_sig(&self) -> Result<()> {
+        if let Some(pk) = sig::get_proof_pub_key(&self.ed25519_pub_key)? {
+            for snapshot in &self.snapshots {
+                sig::verify_sigs_with_pk(&pk, snapshot.records())?;
+            }
+            println!("{}", "verify snapshot signature");
+            sig::verify_sigs_with_pk(&pk, &self.snapshots)?;
+        }
+        Ok(())
+    }
+}
+
+/// proof of pnl between two snapshots, the snapshots in between stay private
+#[derive(Serialize, Deserialize)]
+pub struct WindowProof {
+    pub protocol_version: u32,
+    pub transcript: String,
+    pub ed25519_pub_key: String,
+    pub r1cs_proof: String,
+    /// pnl from start snapshot to end snapshot
+    pub pnl: f64,
+    /// market price at the time of each record in window
+    pub record_prices: Vec<Option<PriceMap>>,
+    /// records after start snapshot until end snapshot
+    pub records: Vec<BlindedRecord>,
+    pub start_snapshot: BlindedSnapshot,
+    pub end_snapshot: BlindedSnapshot,
+}
+
+impl WindowProof {
+    pub fn new(start: &Snapshot, end: &Snapshot, records: &[Record], market_prices: &[MarketPrice]) -> WindowProof {
+        let mut r1cs_config = R1CSConfig::default();
+        let mut prover = r1cs_config.make_prover();
+
+        let mut trade_map = collection::get_i64_trade_map(records);
+        core::inherit_portfolio(&start.snapshot_blnd.portfolio, &mut trade_map, &start.snapshot_blnd.market_price);
+        let pnl = core::calc_total_pnl::<i64, i64>(&trade_map, &end.snapshot_blnd.market_price).deintegerize();
+
+        let record_prices = get_record_prices(records, market_prices);
+        let record_vars = prover.commit_records(records);
+        let start_vars = prover.commit_snapshot(start);
+        let end_vars = prover.commit_snapshot(end);
+        // records of start snapshot are before the window, only its last record anchors the window
+        let start_snapshot = BlindedSnapshot::from(start);
+        let end_snapshot = BlindedSnapshot::with_records(end);
+        let records: Vec<BlindedRecord> = records.iter().map(BlindedRecord::from).collect();
+        let trades = circuit::constrain_trades(&mut prover, Some((&start_snapshot, &start_vars)), &records, &record_vars, &record_prices, &end_snapshot.market_price).unwrap();
+        constrain::equal(&mut prover, trades.pnl(&end_snapshot.market_price), Scalar::integerize(pnl));
+        circuit::constrain_portfolio(&mut prover, &end_vars, &trades.trade_map);
+
+        let bp_gens = make_bp_gens(&prover);
+        WindowProof {
+            protocol_version: constants::PROTOCOL_VERSION,
+            transcript: ZKPNL_CONFIG.transcript.to_string(),
+            ed25519_pub_key: sig::get_pub_key_str(),
+            r1cs_proof: base64::encode(&prover.prove(&bp_gens).unwrap().to_bytes()),
+            pnl,
+            record_prices,
+            records,
+            start_snapshot,
+            end_snapshot,
+        }
+    }
+
+    pub fn verify_r1cs(&self) -> Result<()> {
+        let mut r1cs_config = R1CSConfig::from_transcript(&self.transcript);
+        let mut verifier = r1cs_config.make_verifier();
+
+        let record_vars = verifier.commit_records(&self.records);
+        let start_vars = verifier.commit_snapshot(&self.start_snapshot.msg);
+        let end_vars = verifier.commit_snapshot(&self.end_snapshot.msg);
+        let trades = circuit::constrain_trades(&mut verifier, Some((&self.start_snapshot, &start_vars)), &self.records, &record_vars, &self.record_prices, &self.end_snapshot.market_price)?;
+        constrain::equal(&mut verifier, trades.pnl(&self.end_snapshot.market_price), Scalar::integerize(self.pnl));
+        circuit::constrain_portfolio(&mut verifier, &end_vars, &trades.trade_map);
+
+        println!("{}", "verify r1cs proof");
+        verify_r1cs_proof(verifier, &self.r1cs_proof)
+    }
+
+    /// records should continue from start snapshot and end with the records of end snapshot
+    pub fn verify_hash(&self) -> Result<()> {
+        digest::verify_msg_hashes(&self.records);
+        digest::verify_hash_chain(&self.records);
+        // snapshots taken by earlier versions are anchored by their own last record
+        let anchor = self.start_snapshot.msg.last_record.as_ref().or(self.start_snapshot.msg.records.last().map(|r|&r.hash));
+        match (anchor, self.records.first()) {
+            (Some(anchor), Some(first)) => if first.prev_hash() != anchor.as_str() {
+                panic!("verify hash chain failed at {}", first.hash)
+            },
+            (None, Some(first)) => panic!("record {} is not anchored to start snapshot", first.hash),
+            _ => {},
+        }
+        let end_records = self.end_snapshot.records();
+        if end_records.len() > self.records.len() || self.records.iter().rev().zip(end_records.iter().rev()).any(|(a, b)|a.hash != b.hash) {
+            panic!("records of end snapshot mismatched")
+        }
+        // published record time may be coarsened to a bucket starting before start snapshot
+        if self.records.iter().any(|r|r.msg.time > self.end_snapshot.msg.time) {
+            panic!("record out of window found")
+        }
+        println!("{}", "verify snapshot hash");
+        digest::verify_msg_hash(&self.start_snapshot);
+        digest::verify_msg_hash(&self.end_snapshot);
+        self.end_snapshot.verify_records_root();
+        self.start_snapshot.verify_market_price();
+        self.end_snapshot.verify_market_price();
+        Ok(())
+    }
+
+    pub fn verify_sig(&self) -> Result<()> {
+        if let Some(pk) = sig::get_proof_pub_key(&self.ed25519_pub_key)? {
+            sig::verify_sigs_with_pk(&pk, &self.records)?;
+            println!("{}", "verify snapshot signature");
+            sig::verify_sig_with_pk(&pk, &self.start_snapshot)?;
+            sig::verify_sig_with_pk(&pk, &self.end_snapshot)?;
+        }
+        Ok(())
+    }
+}
+
+/// proof that committed portfolio of a snapshot at its signed market price plus cash is no less than published liabilities,
+/// cash is only linked to the portfolio by the snapshot proof, so the snapshot should be in a verified snapshot chain
+#[derive(Serialize, Deserialize)]
+pub struct SolvencyProof {
+    pub protocol_version: u32,
+    pub transcript: String,
+    pub ed25519_pub_key: String,
+    pub r1cs_proof: String,
+    pub liabilities: f64,
+    pub snapshot: BlindedSnapshot,
+}
+
+impl SolvencyProof {
+    pub fn new(snapshot: &Snapshot, liabilities: f64) -> SolvencyProof {
+        let mut r1cs_config = R1CSConfig::default();
+        let mut prover = r1cs_config.make_prover();
+
+        let vars = prover.commit_snapshot(snapshot);
+        let blinded = BlindedSnapshot::from(snapshot);
+        circuit::constrain_solvency(&mut prover, &blinded, &vars, liabilities).unwrap();
+
+        let bp_gens = make_bp_gens(&prover);
+        SolvencyProof {
+            protocol_version: constants::PROTOCOL_VERSION,
+            transcript: ZKPNL_CONFIG.transcript.to_string(),
+            ed25519_pub_key: sig::get_pub_key_str(),
+            r1cs_proof: base64::encode(&prover.prove(&bp_gens).unwrap().to_bytes()),
+            liabilities,
+            snapshot: blinded,
+        }
+    }
+
+    pub fn verify_r1cs(&self) -> Result<()> {
+        let mut r1cs_config = R1CSConfig::from_transcript(&self.transcript);
+        let mut verifier = r1cs_config.make_verifier();
+
+        let vars = verifier.commit_snapshot(&self.snapshot.msg);
+        circuit::constrain_solvency(&mut verifier, &self.snapshot, &vars, self.liabilities)?;
+
+        println!("{}", "verify r1cs proof");
+        verify_r1cs_proof(verifier, &self.r1cs_proof)
+    }
+
+    pub fn verify_hash(&self) -> Result<()> {
+        println!("{}", "verify snapshot hash");
+        digest::verify_msg_hash(&self.snapshot);
+        self.snapshot.verify_market_price();
+        Ok(())
+    }
+
+    pub fn verify_sig(&self) -> Result<()> {
+        if let Some(pk) = sig::get_proof_pub_key(&self.ed25519_pub_key)? {
+            println!("{}", "verify snapshot signature");
+            sig::verify_sig_with_pk(&pk, &self.snapshot)?;
+        }
+        Ok(())
+    }
+
+    /// snapshot should be proven by a proof of a snapshot chain verified by `ZKPNLProof::verify_chain`
+    pub fn verify_chain(&self, proofs: &[ZKPNLProof]) -> Result<()> {
+        println!("{}", "verify snapshot in snapshot chain");
+        if !proofs.iter().any(|p|p.current_snapshot.hash == self.snapshot.hash) {
+            panic!("snapshot {} is not proven by snapshot chain", self.snapshot.hash)
+        }
+        Ok(())
+    }
+}
+
+/// proof that at least `positive` of the period pnls of consecutive snapshots are non-negative,
+/// pnl commitments of the snapshots are proven by their own snapshot proofs
+#[derive(Serialize, Deserialize)]
+pub struct HitRateProof {
+    pub protocol_version: u32,
+    pub transcript: String,
+    pub ed25519_pub_key: String,
+    pub r1cs_proof: String,
+    pub positive: u64,
+    pub snapshots: Vec<BlindedSnapshot>,
+}
+
+impl HitRateProof {
+    pub fn new(snapshots: &[Snapshot], positive: u64) -> HitRateProof {
+        let mut r1cs_config = R1CSConfig::default();
+        let mut prover = r1cs_config.make_prover();
+
+        let pnls: Vec<(LinearCombination, Option<i64>)> = snapshots.iter().map(|s|{
+            match &s.snapshot_blnd.pnl {
+                Some(opening) => (prover.commit_opening(opening), Some(opening.value)),
+                None => (Scalar::integerize(s.pnl()).into(), Some(i64::integerize(s.pnl()))),
+            }
+        }).collect();
+        constrain::at_least_non_negative(&mut prover, &pnls, positive).unwrap();
+
+        let bp_gens = make_bp_gens(&prover);
+        HitRateProof {
+            protocol_version: constants::PROTOCOL_VERSION,
+            transcript: ZKPNL_CONFIG.transcript.to_string(),
+            ed25519_pub_key: sig::get_pub_key_str(),
+            r1cs_proof: base64::encode(&prover.prove(&bp_gens).unwrap().to_bytes()),
+            positive,
+            snapshots: snapshots.iter().map(BlindedSnapshot::from).collect(),
+        }
+    }
+
+    pub fn verify_r1cs(&self) -> Result<()> {
+        let mut r1cs_config = R1CSConfig::from_transcript(&self.transcript);
+        let mut verifier = r1cs_config.make_verifier();
+
+        let pnls: Vec<(LinearCombination, Option<i64>)> = self.snapshots.iter().map(|s|{
+            match (&s.msg.pnl_cmt, s.msg.pnl) {
+                (Some(cmt), _) => (verifier.commit_cmt(cmt), None),
+                (None, Some(pnl)) => (Scalar::integerize(pnl).into(), None),
+                _ => panic!("snapshot has neither P&L nor its commitment"),
+            }
+        }).collect();
+        constrain::at_least_non_negative(&mut verifier, &pnls, self.positive)?;
+
+        println!("verify r1cs proof of {} snapshots", self.snapshots.len());
+        verify_r1cs_proof(verifier, &self.r1cs_proof)
+    }
+
+    pub fn verify_hash(&self) -> Result<()> {
+        println!("{}", "verify snapshot hash");
+        digest::verify_msg_hashes(&self.snapshots);
+        digest::verify_hash_chain(&self.snapshots);
+        Ok(())
+    }
+
+    pub fn verify_sig(&self) -> Result<()> {
+        if let Some(pk) = sig::get_proof_pub_key(&self.ed25519_pub_key)? {
+            println!("{}", "verify snapshot signature");
+            sig::verify_sigs_with_pk(&pk, &self.snapshots)?;
+        }
+        Ok(())
+    }
+}
+
+/// openings of chosen positions of a snapshot, disclosed to an auditor
+#[derive(Serialize, Deserialize)]
+pub struct PortfolioOpening {
+    pub protocol_version: u32,
+    pub ed25519_pub_key: String,
+    pub openings: LinkedHashMap<String, Opening>,
+    pub snapshot: BlindedSnapshot,
+}
+
+impl PortfolioOpening {
+    pub fn new(snapshot: &Snapshot, symbols: &[&str]) -> PortfolioOpening {
+        let blnd = &snapshot.snapshot_blnd;
+        let openings = symbols.iter().map(|symbol|{
+            let opening = Opening { value: blnd.portfolio[*symbol], blnd: blnd.portfolio_blnd[*symbol].clone() };
+            (symbol.to_string(), opening)
+        }).collect();
+        PortfolioOpening {
+            protocol_version: constants::PROTOCOL_VERSION,
+            ed25519_pub_key: sig::get_pub_key_str(),
+            openings,
+            snapshot: BlindedSnapshot::from(snapshot),
+        }
+    }
+
+    pub fn verify_opening(&self) -> Result<()> {
+        println!("{}", "verify position openings");
+        for (symbol, opening) in &self.openings {
+            let cmt = self.snapshot.msg.portfolio.get(symbol).unwrap_or_else(||panic!("{} not found in snapshot portfolio", symbol));
+            if !extension::opens_to(cmt, opening.value, &opening.blnd) {
+                panic!("opening of {} mismatched with its commitment", symbol)
+            }
+        }
+        Ok(())
+    }
+
+    pub fn verify_hash(&self) -> Result<()> {
+        println!("{}", "verify snapshot hash");
+        digest::verify_msg_hash(&self.snapshot);
+        Ok(())
+    }
+
+    pub fn verify_sig(&self) -> Result<()> {
+        if let Some(pk) = sig::get_proof_pub_key(&self.ed25519_pub_key)? {
+            println!("{}", "verify snapshot signature");
+            sig::verify_sig_with_pk(&pk, &self.snapshot)?;
+        }
+        Ok(())
+    }
+}
+
+/// proof that a record is included in the merkle root of a snapshot, other records stay private
+#[derive(Serialize, Deserialize)]
+pub struct TradeInclusion {
+    pub protocol_version: u32,
+    pub ed25519_pub_key: String,
+    pub record: BlindedRecord,
+    pub path: Vec<digest::MerkleNode>,
+    pub snapshot: BlindedSnapshot,
+}
+
+impl TradeInclusion {
+    pub fn new(snapshot: &Snapshot, record: &Record, path: Vec<digest::MerkleNode>) -> TradeInclusion {
+        TradeInclusion {
+            protocol_version: constants::PROTOCOL_VERSION,
+            ed25519_pub_key: sig::get_pub_key_str(),
+            record: BlindedRecord::from(record),
+            path,
+            snapshot: BlindedSnapshot::from(snapshot),
+        }
+    }
+
+    pub fn verify_hash(&self) -> Result<()> {
+        digest::verify_msg_hash(&self.record);
+        println!("{}", "verify snapshot hash");
+        digest::verify_msg_hash(&self.snapshot);
+        println!("{}", "verify merkle path");
+        let root = self.snapshot.msg.records_root.as_ref().expect("snapshot has no records root");
+        digest::verify_merkle_path(&self.record.hash, &self.path, root);
+        Ok(())
+    }
+
+    pub fn verify_sig(&self) -> Result<()> {
+        if let Some(pk) = sig::get_proof_pub_key(&self.ed25519_pub_key)? {
+            sig::verify_sig_with_pk(&pk, &self.record)?;
+            println!("{}", "verify snapshot signature");
+            sig::verify_sig_with_pk(&pk, &self.snapshot)?;
+        }
+        Ok(())
+    }
+}
+
+/// openings of quantity commitments of records sampled by an auditor's challenge
+#[derive(Serialize, Deserialize)]
+pub struct AuditResponse {
+    pub protocol_version: u32,
+    pub transcript: String,
+    pub ed25519_pub_key: String,
+    pub challenge: String,
+    /// hashes of all records since genesis, which fix the index of each sampled record
+    pub hashes: Vec<String>,
+    /// sampled records in the order of openings
+    pub records: Vec<BlindedRecord>,
+    /// record index with opening of its quantity commitment
+    pub openings: Vec<(usize, Opening)>,
+}
+
+impl AuditResponse {
+    pub fn new(records: &[Record], challenge: &str, count: usize) -> AuditResponse {
+        let indices = digest::sample(challenge, count, records.len());
+        AuditResponse {
+            protocol_version: constants::PROTOCOL_VERSION,
+            transcript: ZKPNL_CONFIG.transcript.to_string(),
+            ed25519_pub_key: sig::get_pub_key_str(),
+            challenge: challenge.to_string(),
+            hashes: records.iter().map(|r|r.hash.clone()).collect(),
+            records: indices.iter().map(|i|BlindedRecord::from(&records[*i])).collect(),
+            openings: indices.iter().map(|i|{
+                let trade = &records[*i].trade;
+                (*i, Opening { value: trade.qty, blnd: trade.qty_blnd.clone() })
+            }).collect(),
+        }
+    }
+
+    /// sampled indices should follow from challenge and the count asked by auditor, so prover can't pick records to open
+    pub fn verify_opening(&self, count: usize) -> Result<()> {
+        println!("{}", "verify sampled record openings");
+        if self.openings.len() != count.min(self.hashes.len()) {
+            panic!("{} records opened, {} asked by auditor", self.openings.len(), count)
+        }
+        let indices: Vec<usize> = self.openings.iter().map(|(i, _)|*i).collect();
+        if indices != digest::sample(&self.challenge, count, self.hashes.len()) {
+            panic!("sampled records mismatched with challenge")
+        }
+        for (record, (_, opening)) in self.records.iter().zip(&self.openings) {
+            if !extension::opens_to(&record.msg.qty, opening.value, &opening.blnd) {
+                panic!("opening mismatched with quantity commitment of {}", record.hash)
+            }
+        }
+        Ok(())
+    }
+
+    /// each sampled record should sit at its index of the hash chain since genesis
+    pub fn verify_hash(&self) -> Result<()> {
+        digest::verify_msg_hashes(&self.records);
+        if self.records.len() != self.openings.len() {
+            panic!("sampled records mismatched with openings")
+        }
+        let genesis_hash = digest::sha256(&self.transcript);
+        for (record, (i, _)) in self.records.iter().zip(&self.openings) {
+            if self.hashes.get(*i) != Some(&record.hash) {
+                panic!("record {} not found at index {}", record.hash, i)
+            }
+            let prev_hash = if *i == 0 { &genesis_hash } else { &self.hashes[*i - 1] };
+            if record.prev_hash() != prev_hash.as_str() {
+                panic!("verify hash chain failed at {}", record.hash)
+            }
+        }
+        Ok(())
+    }
+
+    pub fn verify_sig(&self) -> Result<()> {
+        if let Some(pk) = sig::get_proof_pub_key(&self.ed25519_pub_key)? {
+            sig::verify_sigs_with_pk(&pk, &self.records)?;
+        }
+        Ok(())
+    }
+}
+
+/// latest snapshot of a trader in fund proof, signed by the trader's own key
+#[derive(Serialize, Deserialize)]
+pub struct TraderSnapshot {
+    pub ed25519_pub_key: String,
+    pub snapshot: BlindedSnapshot,
+}
+
+/// total pnl of several traders, proven by opening the sum of their cumulative pnl commitments
+/// so no single trader's pnl is revealed, each snapshot is proven by the trader's own snapshot proof
+#[derive(Serialize, Deserialize)]
+pub struct FundProof {
+    pub protocol_version: u32,
+    /// cumulative pnl of all traders since their first snapshot in integerized unit, as opened from commitments
+    pub pnl: i64,
+    /// sum of blindings of cumulative pnl commitments
+    pub pnl_blnd: String,
+    /// sum of position commitments of each instrument over all traders
+    pub portfolio: PortCmtMap,
+    pub traders: Vec<TraderSnapshot>,
+}
+
+impl FundProof {
+    /// traders are given as pairs of public key and latest snapshot, whose pnl should be committed
+    /// or fund pnl less the published one would tell the pnl of other traders
+    pub fn new(traders: &[(String, Snapshot)]) -> Result<FundProof> {
+        if let Some((pk, _)) = traders.iter().find(|(_, s)|s.msg.pnl.is_some()) {
+            return Err(format!("P&L of trader {} is published, please set pnl_disclosure to keep it private", pk).into())
+        }
+        let openings: Vec<&Opening> = traders.iter().map(|(_, s)|{
+            s.snapshot_blnd.cum_pnl.as_ref().expect("snapshot has no committed cumulative P&L, please take a new snapshot")
+        }).collect();
+        let pnl: i64 = openings.iter().map(|o|o.value).sum();
+        let traders: Vec<TraderSnapshot> = traders.iter().map(|(pk, s)|{
+            TraderSnapshot { ed25519_pub_key: pk.clone(), snapshot: BlindedSnapshot::from(s) }
+        }).collect();
+        Ok(FundProof {
+            protocol_version: constants::PROTOCOL_VERSION,
+            pnl,
+            pnl_blnd: extension::add_blindings(openings.iter().map(|o|&o.blnd)),
+            portfolio: FundProof::sum_portfolio(&traders),
+            traders,
+        })
+    }
+
+    fn sum_portfolio(traders: &[TraderSnapshot]) -> PortCmtMap {
+        let mut symbols: Vec<&String> = vec![];
+        for symbol in traders.iter().flat_map(|t|t.snapshot.msg.portfolio.keys()) {
+            if !symbols.contains(&symbol) { symbols.push(symbol) }
+        }
+        symbols.into_iter().map(|symbol|{
+            let cmts = traders.iter().filter_map(|t|t.snapshot.msg.portfolio.get(symbol));
+            (symbol.clone(), extension::add_commitments(cmts))
+        }).collect()
+    }
+
+    pub fn verify_opening(&self) -> Result<()> {
+        println!("{}", "verify fund P&L opening");
+        let cmts: Vec<&String> = self.traders.iter().map(|t|{
+            t.snapshot.msg.cum_pnl_cmt.as_ref().expect("trader snapshot has no committed cumulative P&L")
+        }).collect();
+        let cmt = extension::add_commitments(cmts);
+        if !extension::opens_to(&cmt, self.pnl, &self.pnl_blnd) {
+            panic!("fund P&L mismatched with sum of trader commitments")
+        }
+        if self.portfolio != FundProof::sum_portfolio(&self.traders) {
+            panic!("fund portfolio mismatched with sum of trader commitments")
+        }
+        Ok(())
+    }
+
+    pub fn verify_hash(&self) -> Result<()> {
+        println!("{}", "verify snapshot hash");
+        for t in &self.traders {
+            digest::verify_msg_hashes(t.snapshot.records());
+            digest::verify_hash_chain(t.snapshot.records());
+            digest::verify_msg_hash(&t.snapshot);
+        }
+        Ok(())
+    }
+
+    /// each trader's snapshot is verified with the trader's own key
+    pub fn verify_sig(&self) -> Result<()> {
+        for t in &self.traders {
+            if t.ed25519_pub_key.is_empty() {
+                panic!("no pub key found for snapshot {}", t.snapshot.hash)
+            }
+            let pk = sig::get_pub_key_from_str(&t.ed25519_pub_key)?;
+            sig::verify_sigs_with_pk(&pk, t.snapshot.records())?;
+            println!("{}", "verify snapshot signature");
+            sig::verify_sig_with_pk(&pk, &t.snapshot)?;
+        }
+        Ok(())
+    }
+}
+
+/// market price of records whose hash is signed in trade message
+fn get_record_prices(records: &[Record], market_prices: &[MarketPrice]) -> Vec<Option<PriceMap>> {
+    records.iter().map(|r|r.msg.price_map_hash.as_ref().map(|_|{
+        market_prices.iter().find(|mp|mp.time == r.trade.time)
+            .expect("market price of record not found").market_price.clone()
+    })).collect()
+}
+
+fn verify_r1cs_proof(verifier: Verifier, r1cs_proof: &str) -> Result<()> {
+    let proof_bytes = base64::decode(r1cs_proof)?;
+    let proof = R1CSProof::from_bytes(&proof_bytes).unwrap();
+    let pc_gens = PedersenGens::default();
+    let bp_gens = make_bp_gens(&verifier);
+    let result = verifier.verify(&proof, &pc_gens, &bp_gens);
+    match result {
+        Ok(()) => println!("{}", "verify OK"),
+        Err(e) => panic!("{}", e),
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{self, Book};
+    use super::*;
+
+    /// three snapshots, the last one below the peak
+    fn book() -> Book {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, &[("XBTUSD", 9000.0), ("ETHUSDT", 200.0)])
+            .snapshot(&[("XBTUSD", 9500.0), ("ETHUSDT", 190.0)])
+            .trade("ETHUSDT", -50, 210.0, &[("XBTUSD", 9600.0), ("ETHUSDT", 210.0)])
+            .snapshot(&[("XBTUSD", 9800.0), ("ETHUSDT", 200.0)])
+            .snapshot(&[("XBTUSD", 9200.0), ("ETHUSDT", 205.0)]);
+        book
+    }
+
+    fn chain(book: &Book, snapshots: &[usize]) -> Vec<ZKPNLProof> {
+        snapshots.iter().map(|i|book.prove(*i, testing::no_claims())).collect()
+    }
+
+    #[test]
+    fn verify_chain_accepts_chain_from_initial_snapshot() {
+        let book = book();
+        let mut proofs = chain(&book, &[2, 0, 1]);
+        ZKPNLProof::verify_chain(&mut proofs).unwrap();
+        for proof in &proofs {
+            assert!(testing::verifies(||proof.verify_r1cs()));
+            assert!(testing::verifies(||proof.verify_hash()));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "snapshot chain does not start from initial snapshot")]
+    fn verify_chain_rejects_chain_without_initial_snapshot() {
+        ZKPNLProof::verify_chain(&mut chain(&book(), &[1, 2])).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "snapshot chain broken")]
+    fn verify_chain_rejects_missing_link() {
+        ZKPNLProof::verify_chain(&mut chain(&book(), &[0, 2])).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "max drawdown claim changed")]
+    fn verify_chain_rejects_changed_drawdown_claim() {
+        let mut proofs = chain(&book(), &[0, 1]);
+        proofs[1].claims.max_drawdown = Some(1.0);
+        ZKPNLProof::verify_chain(&mut proofs).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "performance fee rate changed")]
+    fn verify_chain_rejects_changed_fee_rate() {
+        let mut proofs = chain(&book(), &[0, 1]);
+        proofs[0].claims.performance_fee = Some(0.2);
+        proofs[1].claims.performance_fee = Some(0.1);
+        ZKPNLProof::verify_chain(&mut proofs).unwrap();
+    }
+
+    #[test]
+    fn zkpnl_proof_verifies_after_json_round_trip() {
+        let proof = book().prove(1, testing::no_claims());
+        let proof: ZKPNLProof = serde_json::from_str(&serde_json::to_string(&proof).unwrap()).unwrap();
+        assert!(testing::verifies(||proof.verify_r1cs()));
+        assert!(testing::verifies(||proof.verify_hash()));
+    }
+
+    #[test]
+    fn zkpnl_proof_fails_with_tampered_record_commitment() {
+        let mut proof = book().prove(1, testing::no_claims());
+        let qty = proof.current_snapshot.records()[0].msg.qty.clone();
+        proof.current_snapshot.msg.records[0].msg.pnl = qty;
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+        assert!(!testing::verifies(||proof.verify_hash()));
+    }
+
+    #[test]
+    fn aggregate_proof_verifies_whole_album_after_json_round_trip() {
+        let book = book();
+        let proof = AggregateProof::new(&book.album, &book.market_prices);
+        let proof: AggregateProof = serde_json::from_str(&serde_json::to_string(&proof).unwrap()).unwrap();
+        assert_eq!(proof.snapshots.len(), 3);
+        assert!(testing::verifies(||proof.verify_r1cs()));
+        assert!(testing::verifies(||proof.verify_hash()));
+    }
+
+    #[test]
+    fn aggregate_proof_fails_with_tampered_pnl() {
+        let book = book();
+        let mut proof = AggregateProof::new(&book.album, &book.market_prices);
+        proof.snapshots[1].msg.pnl = proof.snapshots[1].msg.pnl.map(|pnl|pnl + 1.0);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+        assert!(!testing::verifies(||proof.verify_hash()));
+    }
+
+    #[test]
+    fn aggregate_proof_fails_without_middle_snapshot() {
+        let book = book();
+        let mut proof = AggregateProof::new(&book.album, &book.market_prices);
+        proof.snapshots.remove(1);
+        proof.record_prices.remove(1);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+        assert!(!testing::verifies(||proof.verify_hash()));
+    }
+
+    fn window(book: &Book, start: usize, end: usize) -> WindowProof {
+        let (start, end) = (&book.album[start], &book.album[end]);
+        let records: Vec<Record> = book.records.iter()
+            .filter(|r|r.trade.time > start.msg.time && r.trade.time <= end.msg.time).cloned().collect();
+        WindowProof::new(start, end, &records, &book.market_prices)
+    }
+
+    #[test]
+    fn window_proof_verifies_pnl_between_snapshots_after_json_round_trip() {
+        let book = book();
+        let proof = window(&book, 0, 2);
+        let pnl = book.album[1].msg.pnl.unwrap() + book.album[2].msg.pnl.unwrap();
+        assert!((proof.pnl - pnl).abs() < 1e-6);
+        let proof: WindowProof = serde_json::from_str(&serde_json::to_string(&proof).unwrap()).unwrap();
+        assert!(testing::verifies(||proof.verify_r1cs()));
+        assert!(testing::verifies(||proof.verify_hash()));
+    }
+
+    #[test]
+    fn window_proof_leaves_out_records_of_start_snapshot() {
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, &[("XBTUSD", 9000.0)]).snapshot(&[("XBTUSD", 9500.0)]).merkle_records()
+            .trade("XBTUSD", -40, 9600.0, &[("XBTUSD", 9600.0)]).snapshot(&[("XBTUSD", 9800.0)]).merkle_records();
+        let proof = window(&book, 0, 1);
+        assert!(proof.start_snapshot.records.is_empty());
+        assert_eq!(proof.end_snapshot.records.len(), 1);
+        assert!(testing::verifies(||proof.verify_r1cs()));
+        assert!(testing::verifies(||proof.verify_hash()));
+    }
+
+    #[test]
+    fn window_proof_fails_with_tampered_pnl() {
+        let mut proof = window(&book(), 0, 2);
+        proof.pnl += 1.0;
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    #[should_panic(expected = "records of end snapshot mismatched")]
+    fn window_proof_rejects_missing_record() {
+        let mut proof = window(&book(), 0, 1);
+        proof.records.clear();
+        proof.record_prices.clear();
+        proof.verify_hash().unwrap();
+    }
+
+    /// periods of pnl +50000, +30500 and -60250, each hidden behind a claim
+    fn hit_rate_book() -> Book {
+        let claim = PnlClaim { lower: None, upper: None };
+        let mut book = Book::new();
+        book.trade("XBTUSD", 100, 9000.0, &[("XBTUSD", 9000.0), ("ETHUSDT", 200.0)])
+            .snapshot(&[("XBTUSD", 9500.0), ("ETHUSDT", 190.0)]).disclose(claim)
+            .trade("ETHUSDT", -50, 210.0, &[("XBTUSD", 9600.0), ("ETHUSDT", 210.0)])
+            .snapshot(&[("XBTUSD", 9800.0), ("ETHUSDT", 200.0)]).disclose(claim)
+            .snapshot(&[("XBTUSD", 9200.0), ("ETHUSDT", 205.0)]).disclose(claim);
+        book
+    }
+
+    #[test]
+    fn hit_rate_proof_verifies_positive_periods_after_json_round_trip() {
+        let proof = HitRateProof::new(&hit_rate_book().album, 2);
+        let proof: HitRateProof = serde_json::from_str(&serde_json::to_string(&proof).unwrap()).unwrap();
+        assert!(proof.snapshots.iter().all(|s|s.msg.pnl.is_none()));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+        assert!(testing::verifies(||proof.verify_hash()));
+    }
+
+    #[test]
+    fn hit_rate_proof_fails_with_raised_count() {
+        let mut proof = HitRateProof::new(&hit_rate_book().album, 2);
+        proof.positive = 3;
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    fn hit_rate_proof_fails_without_snapshots() {
+        let mut proof = HitRateProof::new(&hit_rate_book().album, 2);
+        proof.snapshots.clear();
+        assert!(proof.verify_r1cs().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "verify hash chain failed")]
+    fn hit_rate_proof_rejects_missing_middle_snapshot() {
+        let mut proof = HitRateProof::new(&hit_rate_book().album, 1);
+        proof.snapshots.remove(1);
+        proof.verify_hash().unwrap();
+    }
+
+    /// capital plus cumulative pnl of a snapshot, which its portfolio at market price plus cash adds up to
+    fn equity(snapshot: &Snapshot) -> f64 {
+        snapshot.capital() + snapshot.snapshot_blnd.cum_pnl.as_ref().unwrap().value.deintegerize()
+    }
+
+    #[test]
+    fn solvency_proof_verifies_portfolio_at_market_price_plus_cash() {
+        let book = book();
+        let snapshot = &book.album[2];
+        let proof = SolvencyProof::new(snapshot, equity(snapshot) - 0.01);
+        let proof: SolvencyProof = serde_json::from_str(&serde_json::to_string(&proof).unwrap()).unwrap();
+        assert!(testing::verifies(||proof.verify_hash()));
+        assert!(testing::verifies(||proof.verify_r1cs()));
+        assert!(testing::verifies(||proof.verify_chain(&chain(&book, &[0, 1, 2]))));
+    }
+
+    #[test]
+    fn solvency_proof_fails_above_assets() {
+        let snapshot = &book().album[2];
+        let proof = SolvencyProof::new(snapshot, equity(snapshot) + 0.01);
+        assert!(!testing::verifies(||proof.verify_r1cs()));
+    }
+
+    #[test]
+    #[should_panic(expected = "verify market price failed")]
+    fn solvency_proof_rejects_tampered_market_price() {
+        let mut proof = SolvencyProof::new(&book().album[2], 0.0);
+        proof.snapshot.market_price.insert("XBTUSD".to_string(), 20000.0);
+        proof.verify_hash().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "is not proven by snapshot chain")]
+    fn solvency_proof_rejects_snapshot_out_of_chain() {
+        let book = book();
+        let proof = SolvencyProof::new(&book.album[2], 0.0);
+        proof.verify_chain(&chain(&book, &[0, 1])).unwrap();
+    }
+
+    #[test]
+    fn zkpnl_proof_fails_with_inflated_cash() {
+        let mut book = book();
+        let snapshot = book.album.last_mut().unwrap();
+        let cash = snapshot.snapshot_blnd.cash.as_ref().unwrap().value + 1_000_000_000;
+        let (cash_cmt, cash_blnd) = R1CSConfig::default().make_prover().commit_quantity(cash);
+        snapshot.msg.cash_cmt = Some(cash_cmt);
+        snapshot.snapshot_blnd.cash = Some(Opening { value: cash, blnd: cash_blnd });
+        snapshot.hash = digest::sha256(&String::from(&snapshot.msg));
+        let liabilities = equity(snapshot) + 0.5;
+        assert!(testing::verifies(||SolvencyProof::new(&book.album[2], liabilities).verify_r1cs()));
+        assert!(!testing::verifies(||book.prove(2, testing::no_claims()).verify_r1cs()));
+    }
+
+    #[test]
+    fn portfolio_opening_opens_chosen_positions_after_json_round_trip() {
+        let opening = PortfolioOpening::new(&book().album[2], &["ETHUSDT"]);
+        let opening: PortfolioOpening = serde_json::from_str(&serde_json::to_string(&opening).unwrap()).unwrap();
+        assert_eq!(opening.openings.keys().collect::<Vec<_>>(), vec!["ETHUSDT"]);
+        assert_eq!(opening.openings["ETHUSDT"].value, -50);
+        assert!(testing::verifies(||opening.verify_opening()));
+        assert!(testing::verifies(||opening.verify_hash()));
+    }
+
+    #[test]
+    #[should_panic(expected = "opening of XBTUSD mismatched with its commitment")]
+    fn portfolio_opening_rejects_tampered_size() {
+        let mut opening = PortfolioOpening::new(&book().album[2], &["XBTUSD"]);
+        opening.openings["XBTUSD"].value += 1;
+        opening.verify_opening().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "opening of XBTUSD mismatched with its commitment")]
+    fn portfolio_opening_rejects_blinding_of_other_position() {
+        let mut opening = PortfolioOpening::new(&book().album[2], &["XBTUSD", "ETHUSDT"]);
+        opening.openings["XBTUSD"].blnd = opening.openings["ETHUSDT"].blnd.clone();
+        opening.verify_opening().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "DOGEUSD not found in snapshot portfolio")]
+    fn portfolio_opening_rejects_symbol_out_of_portfolio() {
+        let mut opening = PortfolioOpening::new(&book().album[2], &["XBTUSD"]);
+        let xbt = opening.openings["XBTUSD"].clone();
+        opening.openings.insert("DOGEUSD".to_string(), xbt);
+        opening.verify_opening().unwrap();
+    }
+
+    fn audit(count: usize) -> AuditResponse {
+        let mut book = Book::new();
+        for qty in 1..7 {
+            book.trade("XBTUSD", qty, 9000.0, &[("XBTUSD", 9000.0)]);
+        }
+        AuditResponse::new(&book.records, "challenge", count)
+    }
+
+    #[test]
+    fn audit_response_opens_only_sampled_records_after_json_round_trip() {
+        let response = audit(2);
+        let response: AuditResponse = serde_json::from_str(&serde_json::to_string(&response).unwrap()).unwrap();
+        assert_eq!((response.hashes.len(), response.records.len()), (6, 2));
+        for (record, (i, opening)) in response.records.iter().zip(&response.openings) {
+            assert_eq!(record.hash, response.hashes[*i]);
+            assert_eq!(opening.value, *i as i64 + 1);
+        }
+        assert!(testing::verifies(||response.verify_hash()));
+        assert!(testing::verifies(||response.verify_opening(2)));
+    }
+
+    #[test]
+    fn audit_response_opens_all_records_below_count() {
+        let response = audit(10);
+        assert_eq!(response.openings.len(), 6);
+        assert!(testing::verifies(||response.verify_opening(10)));
+    }
+
+    #[test]
+    #[should_panic(expected = "2 records opened, 3 asked by auditor")]
+    fn audit_response_rejects_fewer_openings_than_asked() {
+        audit(2).verify_opening(3).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "sampled records mismatched with challenge")]
+    fn audit_response_rejects_records_picked_by_prover() {
+        let mut response = audit(2);
+        response.challenge = "another challenge".to_string();
+        response.verify_opening(2).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "opening mismatched with quantity commitment")]
+    fn audit_response_rejects_tampered_opening() {
+        let mut response = audit(2);
+        response.openings[0].1.value += 1;
+        response.verify_opening(2).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "not found at index")]
+    fn audit_response_rejects_record_at_other_index() {
+        let mut response = audit(2);
+        response.records.swap(0, 1);
+        response.verify_hash().unwrap();
+    }
+
+    /// one trader long XBTUSD and the other short, each with pnl committed
+    fn traders() -> Vec<(String, Snapshot)> {
+        let claim = PnlClaim { lower: None, upper: None };
+        let mut long = Book::new();
+        long.trade("XBTUSD", 100, 9000.0, &[("XBTUSD", 9000.0)]).snapshot(&[("XBTUSD", 9500.0)]).disclose(claim);
+        let mut short = Book::new();
+        short.trade("XBTUSD", -40, 9100.0, &[("XBTUSD", 9100.0)]).snapshot(&[("XBTUSD", 9500.0)]).disclose(claim);
+        vec![("long".to_string(), long.album.pop().unwrap()), ("short".to_string(), short.album.pop().unwrap())]
+    }
+
+    #[test]
+    fn fund_proof_opens_sum_of_trader_pnl() {
+        let traders = traders();
+        let proof = FundProof::new(&traders).unwrap();
+        let proof: FundProof = serde_json::from_str(&serde_json::to_string(&proof).unwrap()).unwrap();
+        let pnl: i64 = traders.iter().map(|(_, s)|s.snapshot_blnd.cum_pnl.as_ref().unwrap().value).sum();
+        assert_eq!(proof.pnl, pnl);
+        assert!(proof.traders.iter().all(|t|t.snapshot.msg.pnl.is_none()));
+        assert!(testing::verifies(||proof.verify_opening()));
+        assert!(testing::verifies(||proof.verify_hash()));
+    }
+
+    #[test]
+    fn fund_proof_refuses_published_pnl() {
+        let mut traders = traders();
+        let mut book = Book::new();
+        book.trade("XBTUSD", 10, 9000.0, &[("XBTUSD", 9000.0)]).snapshot(&[("XBTUSD", 9500.0)]);
+        traders.push(("public".to_string(), book.album.pop().unwrap()));
+        assert!(FundProof::new(&traders).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "fund P&L mismatched with sum of trader commitments")]
+    fn fund_proof_rejects_tampered_pnl() {
+        let mut proof = FundProof::new(&traders()).unwrap();
+        proof.pnl += 1;
+        proof.verify_opening().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "fund portfolio mismatched with sum of trader commitments")]
+    fn fund_proof_rejects_tampered_portfolio() {
+        let mut proof = FundProof::new(&traders()).unwrap();
+        let cmt = proof.traders[0].snapshot.msg.portfolio["XBTUSD"].clone();
+        proof.portfolio.insert("XBTUSD".to_string(), cmt);
+        proof.verify_opening().unwrap();
+    }
 }
\ No newline at end of file
@@ -19,6 +19,15 @@ pub struct SNPReport {
     pub log_return: f64,
 }
 
+/// fee charged by a snapshot, as proven in its snapshot proof
+#[derive(Serialize)]
+pub struct FeeReport {
+    pub hash: String,
+    pub time: DateTime<FixedOffset>,
+    pub rate: f64,
+    pub fee: f64,
+}
+
 #[derive(PartialEq, PartialOrd)]
 pub struct PNLReport {
     pub is_option: bool,
@@ -43,13 +52,23 @@ impl SNPReport {
         SNPReport {
             hash: snapshot.hash.clone(),
             time: snapshot.msg.time,
-            capital: snapshot.msg.capital,
-            pnl: snapshot.msg.pnl,
-            log_return: snapshot.msg.log_return,
+            capital: snapshot.capital(),
+            pnl: snapshot.pnl(),
+            log_return: snapshot.log_return(),
         }
     }
 }
 
+impl FeeReport {
+    pub fn new(snapshot: &Snapshot, rate: f64) -> Option<FeeReport> {
+        snapshot.msg.fee.map(|fee|FeeReport {
+            hash: snapshot.hash.clone(),
+            time: snapshot.msg.time,
+            rate, fee,
+        })
+    }
+}
+
 impl PNLReport {
     fn new(symbol: &str, trades: &[(f64, i64)], price_map: &PriceMap) -> PNLReport {
         let is_option = ZKPNL_CONFIG.is_option(symbol);
@@ -118,22 +137,22 @@ impl RangeFilteredTradeMap {
     pub fn new(range: &TimeRange, records: &[Record]) -> Option<RangeFilteredTradeMap> {
         let (start, end) = match range {
             Range(start, end) => (*start, *end),
-            UpToLastSince(start) | UpToNowSince(start) => (*start, records.last().unwrap().msg.time),
-            UpTo(end) => (records.first().unwrap().msg.time, *end),
-            UpToLast | UpToNow => (records.first().unwrap().msg.time, records.last().unwrap().msg.time)
+            UpToLastSince(start) | UpToNowSince(start) => (*start, records.last().unwrap().trade.time),
+            UpTo(end) => (records.first().unwrap().trade.time, *end),
+            UpToLast | UpToNow => (records.first().unwrap().trade.time, records.last().unwrap().trade.time)
         };
         let mut slice1_len = 0usize;
         let mut time_vec: Vec<DateTime<FixedOffset>> = vec![];
         let mut plain_trade_map1: I64TradeMap = LinkedHashMap::new();
         let mut plain_trade_map2: I64TradeMap = LinkedHashMap::new();
         for r in records {
-            if r.msg.time < start {
+            if r.trade.time < start {
                 slice1_len += 1;
                 let plain_trade_vec = plain_trade_map1.entry(r.trade.symbol.clone()).or_insert(vec![]);
                 plain_trade_vec.push((r.trade.price, r.trade.qty));
             }
-            if r.msg.time <= end {
-                time_vec.push(r.msg.time);
+            if r.trade.time <= end {
+                time_vec.push(r.trade.time);
                 let plain_trade_vec = plain_trade_map2.entry(r.trade.symbol.clone()).or_insert(vec![]);
                 plain_trade_vec.push((r.trade.price, r.trade.qty));
             }
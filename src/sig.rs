@@ -73,6 +73,16 @@ pub fn get_pub_key_from_str(s: &str) -> Result<PublicKey> {
     Ok(PublicKey::new(pk_bytes))
 }
 
+/// public key carried in a proof, none if the proof carries no key so signatures are not verified
+pub fn get_proof_pub_key(s: &str) -> Result<Option<PublicKey>> {
+    if s.is_empty() {
+        println!("{}", "warning: no pub key found in proof, skip signature verification.");
+        Ok(None)
+    } else {
+        get_pub_key_from_str(s).map(Some)
+    }
+}
+
 fn get_sig(s: &str) -> Result<Signature> {
     let sig_vec = base64::decode(s)?;
     assert_eq!(sig_vec.len(), 64, "signature length incorrect");
@@ -0,0 +1,211 @@
+//! fixtures shared by unit tests, taken under the default config.toml
+
+use chrono::{DateTime, Duration, FixedOffset};
+use crate::Result;
+use crate::circuit::Claims;
+use crate::core::{self, Deintegerize, Integerize};
+use crate::{digest, sig};
+use crate::extension::{ProverExt, R1CSConfig};
+use crate::model::*;
+use crate::proof::ZKPNLProof;
+
+/// records and snapshots of one trader, a minute apart
+pub struct Book {
+    pub records: Vec<Record>,
+    pub album: Vec<Snapshot>,
+    pub market_prices: Vec<MarketPrice>,
+    minutes: i64,
+}
+
+impl Book {
+    pub fn new() -> Book {
+        Book { records: vec![], album: vec![], market_prices: vec![], minutes: 0 }
+    }
+
+    fn tick(&mut self) -> DateTime<FixedOffset> {
+        self.minutes += 1;
+        DateTime::parse_from_rfc3339("2020-01-01T00:00:00+00:00").unwrap() + Duration::minutes(self.minutes)
+    }
+
+    /// let time pass without trading
+    pub fn wait(&mut self, minutes: i64) -> &mut Book {
+        self.minutes += minutes;
+        self
+    }
+
+    pub fn commit(&mut self, r#type: TradeType, symbol: &str, qty: i64, price: f64, market_price: &[(&str, f64)]) -> &mut Book {
+        let time = self.tick();
+        let market_price = price_map(market_price);
+        let record = Record::new(time, symbol, qty, price, r#type, &self.records, &market_price).unwrap();
+        self.records.push(record);
+        self.market_prices.push(MarketPrice { time, market_price });
+        self
+    }
+
+    pub fn trade(&mut self, symbol: &str, qty: i64, price: f64, market_price: &[(&str, f64)]) -> &mut Book {
+        self.commit(TradeType::Trade, symbol, qty, price, market_price)
+    }
+
+    /// commit price of last record as hide_price does, taking its market price out of proofs
+    pub fn hide_price(&mut self, price: f64) -> &mut Book {
+        let record = self.records.last_mut().unwrap();
+        let (price_cmt, price_blnd) = R1CSConfig::default().make_prover().commit_quantity(i64::integerize(price));
+        record.msg.price = None;
+        record.msg.price_cmt = Some(price_cmt);
+        record.msg.price_map_hash = None;
+        record.trade.price = price;
+        record.trade.price_blnd = Some(price_blnd);
+        self
+    }
+
+    /// commit symbol of last record as hide_symbol does
+    pub fn hide_symbol(&mut self) -> &mut Book {
+        let record = self.records.last_mut().unwrap();
+        recommit_symbol(record, &record.trade.symbol.clone());
+        self
+    }
+
+    pub fn snapshot(&mut self, market_price: &[(&str, f64)]) -> &mut Book {
+        let time = self.tick();
+        let snapshot = Snapshot::new(time, &self.album, self.records.clone(), &price_map(market_price)).unwrap();
+        self.album.push(snapshot);
+        self
+    }
+
+    /// hide pnl of last snapshot behind a claim as pnl_disclosure does, before next snapshot links to it
+    pub fn disclose(&mut self, claim: PnlClaim) -> &mut Book {
+        let snapshot = self.album.last_mut().unwrap();
+        let pnl = i64::integerize(snapshot.msg.pnl.take().unwrap());
+        let (pnl_cmt, pnl_blnd) = R1CSConfig::default().make_prover().commit_quantity(pnl);
+        snapshot.msg.log_return = None;
+        snapshot.msg.pnl_cmt = Some(pnl_cmt);
+        snapshot.msg.pnl_claim = Some(claim);
+        snapshot.snapshot_blnd.pnl = Some(Opening { value: pnl, blnd: pnl_blnd });
+        snapshot.hash = digest::sha256(&String::from(&snapshot.msg));
+        snapshot.sig = sig::sign(&snapshot.hash).unwrap();
+        self
+    }
+
+    /// commit capital and pnl of last snapshot and publish return in place of pnl as hide_capital does,
+    /// cash is moved along with capital so it still adds up
+    pub fn hide_capital(&mut self, capital: f64) -> &mut Book {
+        let snapshot = self.album.last_mut().unwrap();
+        let mut config = R1CSConfig::default();
+        let mut prover = config.make_prover();
+        let (capital, pnl) = (i64::integerize(capital), i64::integerize(snapshot.pnl()));
+        let (capital_cmt, capital_blnd) = prover.commit_quantity(capital);
+        let (pnl_cmt, pnl_blnd) = prover.commit_quantity(pnl);
+        let cash = snapshot.snapshot_blnd.cash.as_ref().unwrap().value + capital - i64::integerize(snapshot.capital());
+        let (cash_cmt, cash_blnd) = prover.commit_quantity(cash);
+        snapshot.msg.capital = None;
+        snapshot.msg.capital_cmt = Some(capital_cmt);
+        snapshot.msg.r#return = Some(core::calc_return(pnl, capital).deintegerize());
+        snapshot.msg.pnl = None;
+        snapshot.msg.log_return = None;
+        snapshot.msg.pnl_cmt = Some(pnl_cmt);
+        snapshot.msg.cash_cmt = Some(cash_cmt);
+        snapshot.snapshot_blnd.capital = Some(Opening { value: capital, blnd: capital_blnd });
+        snapshot.snapshot_blnd.pnl = Some(Opening { value: pnl, blnd: pnl_blnd });
+        snapshot.snapshot_blnd.cash = Some(Opening { value: cash, blnd: cash_blnd });
+        snapshot.hash = digest::sha256(&String::from(&snapshot.msg));
+        snapshot.sig = sig::sign(&snapshot.hash).unwrap();
+        self
+    }
+
+    /// publish change of equity in BTC of last snapshot as btc_pnl does, before next snapshot links to it
+    pub fn btc_pnl(&mut self) -> &mut Book {
+        let equity = |s: &Snapshot| i64::integerize(s.capital()) + s.snapshot_blnd.cum_pnl.as_ref().unwrap().value;
+        let cents = |s: &Snapshot| core::calc_cents(s.snapshot_blnd.market_price["XBTUSD"]);
+        let last = self.album.last().unwrap();
+        // initial snapshot converts starting capital at its own XBTUSD price
+        let (start_equity, start_cents) = match self.album.len() {
+            1 => (i64::integerize(last.capital()), cents(last)),
+            n => (equity(&self.album[n - 2]), cents(&self.album[n - 2])),
+        };
+        let btc_pnl = core::calc_btc_pnl(start_equity, start_cents, equity(last), cents(last));
+        let snapshot = self.album.last_mut().unwrap();
+        snapshot.msg.btc_pnl = Some(btc_pnl.deintegerize());
+        snapshot.hash = digest::sha256(&String::from(&snapshot.msg));
+        snapshot.sig = sig::sign(&snapshot.hash).unwrap();
+        self
+    }
+
+    /// commit records of last snapshot by merkle root as merkle_records does, before next snapshot links to it
+    pub fn merkle_records(&mut self) -> &mut Book {
+        let snapshot = self.album.last_mut().unwrap();
+        let hashes: Vec<String> = snapshot.snapshot_blnd.records.iter().map(|r|r.hash.clone()).collect();
+        snapshot.msg.records_root = Some(digest::merkle_root(&hashes));
+        snapshot.msg.records = vec![];
+        snapshot.hash = digest::sha256(&String::from(&snapshot.msg));
+        snapshot.sig = sig::sign(&snapshot.hash).unwrap();
+        self
+    }
+
+    /// commit running peak of cumulative pnl in every snapshot as max_drawdown does
+    pub fn track_peak(&mut self) -> &mut Book {
+        let mut peak = 0;
+        for snapshot in &mut self.album {
+            peak = peak.max(snapshot.snapshot_blnd.cum_pnl.as_ref().unwrap().value);
+            let (peak_cmt, peak_blnd) = R1CSConfig::default().make_prover().commit_quantity(peak);
+            snapshot.msg.peak_pnl_cmt = Some(peak_cmt);
+            snapshot.snapshot_blnd.peak_pnl = Some(Opening { value: peak, blnd: peak_blnd });
+        }
+        self
+    }
+
+    /// commit high-water mark and charge fee above it in every snapshot as performance_fee does
+    pub fn track_hwm(&mut self, rate: f64) -> &mut Book {
+        let mut hwm = None;
+        for snapshot in &mut self.album {
+            let capital = i64::integerize(snapshot.capital());
+            let equity = capital + snapshot.snapshot_blnd.cum_pnl.as_ref().unwrap().value;
+            let prev_hwm = hwm.unwrap_or(capital);
+            hwm = Some(prev_hwm.max(equity));
+            let (hwm_cmt, hwm_blnd) = R1CSConfig::default().make_prover().commit_quantity(hwm.unwrap());
+            snapshot.msg.hwm_cmt = Some(hwm_cmt);
+            snapshot.msg.fee = Some(core::calc_fee(rate, equity - prev_hwm).deintegerize());
+            snapshot.snapshot_blnd.hwm = Some(Opening { value: hwm.unwrap(), blnd: hwm_blnd });
+        }
+        self
+    }
+
+    /// proof of i-th snapshot against its previous one under given claims
+    pub fn prove(&self, i: usize, claims: Claims) -> ZKPNLProof {
+        let previous = if i > 0 { Some(&self.album[i - 1]) } else { None };
+        ZKPNLProof::with_claims(previous, &self.album[i], &self.market_prices, claims)
+    }
+}
+
+/// replace symbol committed in a record
+pub fn recommit_symbol(record: &mut Record, symbol: &str) {
+    let (symbol_cmt, symbol_blnd) = R1CSConfig::default().make_prover().commit_quantity(digest::symbol_id(symbol));
+    record.msg.symbol = None;
+    record.msg.symbol_cmt = Some(symbol_cmt);
+    record.trade.symbol = symbol.to_string();
+    record.trade.symbol_blnd = Some(symbol_blnd);
+}
+
+pub fn price_map(prices: &[(&str, f64)]) -> PriceMap {
+    prices.iter().map(|(symbol, price)|(symbol.to_string(), *price)).collect()
+}
+
+/// claims with nothing claimed besides pnl
+pub fn no_claims() -> Claims {
+    Claims {
+        position_limit: LimitMap::new(),
+        leverage: None,
+        max_drawdown: None,
+        performance_fee: None,
+        benchmark: None,
+        restricted: vec![],
+        turnover: None,
+    }
+}
+
+/// true if a check passes, verifiers panic on a failed proof
+pub fn verifies<F: FnOnce() -> Result<()>>(check: F) -> bool {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(check)) {
+        Ok(result) => result.is_ok(),
+        Err(_) => false,
+    }
+}
\ No newline at end of file
@@ -1,10 +1,28 @@
-use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc, offset::TimeZone};
+use serde::Deserialize;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Timelike, Utc, offset::TimeZone};
 use crate::{Result, ZKPNL_CONFIG};
 
 pub fn now() -> DateTime<FixedOffset> {
     Utc::now().with_timezone(&time_zone())
 }
 
+/// bucket size of time published in trade message
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    Minute, Hour, Day
+}
+
+/// start of the bucket containing time, day bucket is in the time zone of time
+pub fn coarsen(time: DateTime<FixedOffset>, granularity: Granularity) -> DateTime<FixedOffset> {
+    let minute = time.with_nanosecond(0).unwrap().with_second(0).unwrap();
+    match granularity {
+        Granularity::Minute => minute,
+        Granularity::Hour => minute.with_minute(0).unwrap(),
+        Granularity::Day => minute.with_minute(0).unwrap().with_hour(0).unwrap(),
+    }
+}
+
 pub enum TimeRange {
     Range(DateTime<FixedOffset>, DateTime<FixedOffset>),
     UpToLastSince(DateTime<FixedOffset>),
@@ -53,4 +71,28 @@ impl TimeRange {
 
 fn time_zone() -> FixedOffset {
     FixedOffset::east(ZKPNL_CONFIG.time_zone * 3600)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    #[test]
+    fn coarsen_takes_start_of_bucket() {
+        let t = time("2020-03-01T13:47:29.123+00:00");
+        assert_eq!(coarsen(t, Granularity::Minute), time("2020-03-01T13:47:00+00:00"));
+        assert_eq!(coarsen(t, Granularity::Hour), time("2020-03-01T13:00:00+00:00"));
+        assert_eq!(coarsen(t, Granularity::Day), time("2020-03-01T00:00:00+00:00"));
+    }
+
+    #[test]
+    fn coarsen_day_in_time_zone_of_time() {
+        let t = time("2020-03-01T01:30:00+08:00");
+        assert_eq!(coarsen(t, Granularity::Day), time("2020-03-01T00:00:00+08:00"));
+        assert_eq!(coarsen(t, Granularity::Day).offset(), t.offset());
+    }
 }
\ No newline at end of file